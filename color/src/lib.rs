@@ -25,3 +25,18 @@ macro_rules! yellow {
 		format!("\x1b[33m{}\x1b[39m", $str)
 	}
 }
+
+#[macro_export]
+macro_rules! cyan {
+	($str:expr) => {
+		format!("\x1b[36m{}\x1b[39m", $str)
+	}
+}
+
+/// Bright black, which most terminals show as grey
+#[macro_export]
+macro_rules! grey {
+	($str:expr) => {
+		format!("\x1b[90m{}\x1b[39m", $str)
+	}
+}
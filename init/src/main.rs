@@ -58,7 +58,7 @@ macro_rules! log {
     }};
 }
 
-static  DEFAULT_PATH: &'static str = "/bin /guest/bin";
+static  DEFAULT_PATH: &'static str = "/bin:/guest/bin";
 
 fn main() {
     println!("Init started");
@@ -0,0 +1,365 @@
+//! Integer arithmetic for `$(( ))` with the operators and precedence of C.
+//! Variables can be referenced by name and assigned to with `=`, `+=`, `++` and so on.
+
+use crate::lexer::is_name_char;
+use crate::vars;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+/// Operators, longest first so they are matched greedily
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":", "=", "(", ")", ",",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    'outer: while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if is_name_char(c) {
+            let start = i;
+            while i < chars.len() && is_name_char(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(if c.is_ascii_digit() {
+                Token::Number(parse_number(&word)?)
+            } else {
+                Token::Name(word)
+            });
+            continue;
+        }
+        for op in OPERATORS {
+            let len = op.len();
+            if i + len <= chars.len() && chars[i..i + len].iter().copied().eq(op.chars()) {
+                tokens.push(Token::Op(op));
+                i += len;
+                continue 'outer;
+            }
+        }
+        return Err(format!("syntax error: invalid character `{c}'"));
+    }
+    Ok(tokens)
+}
+
+/// Parse a decimal, `0x` hexadecimal or `0` octal number
+fn parse_number(text: &str) -> Result<i64, String> {
+    let result = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if text.len() > 1 && let Some(octal) = text.strip_prefix('0') {
+        i64::from_str_radix(octal, 8)
+    } else {
+        text.parse()
+    };
+    result.map_err(|_| format!("invalid number `{text}'"))
+}
+
+/// Variables containing expressions are evaluated up to this depth
+const MAX_DEPTH: usize = 64;
+
+/// A value while evaluating, remembers the variable it came from so it can be assigned to
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i64),
+    Variable(String),
+}
+
+/// Binding power of binary operators, higher binds tighter
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "," => 1,
+        "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "<<=" | ">>=" | "&=" | "^=" | "|=" => 2,
+        "?" => 3,
+        "||" => 4,
+        "&&" => 5,
+        "|" => 6,
+        "^" => 7,
+        "&" => 8,
+        "==" | "!=" => 9,
+        "<" | "<=" | ">" | ">=" => 10,
+        "<<" | ">>" => 11,
+        "+" | "-" => 12,
+        "*" | "/" | "%" => 13,
+        "**" => 14,
+        _ => return None,
+    })
+}
+
+struct Evaluator {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Set while parsing the branch of `&&`, `||` or `?:` that is not taken,
+    /// side effects like assignments are skipped
+    skip: bool,
+    /// How many variables deep this expression is
+    depth: usize,
+}
+
+impl Evaluator {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(o)) if o == op => Ok(()),
+            _ => Err(format!("syntax error: expected `{op}'")),
+        }
+    }
+
+    fn value(&self, value: &Value) -> Result<i64, String> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            Value::Variable(_) if self.skip => Ok(0),
+            // The value of a variable may itself be an expression
+            Value::Variable(name) => match vars::get(name) {
+                Some(value) if !value.trim().is_empty() => eval_nested(&value, self.depth + 1),
+                _ => Ok(0),
+            },
+        }
+    }
+
+    fn assign(&self, target: &Value, value: i64) -> Result<i64, String> {
+        let Value::Variable(name) = target else {
+            return Err("attempted assignment to non-variable".to_owned());
+        };
+        if !self.skip {
+            vars::set(name, &value.to_string());
+        }
+        Ok(value)
+    }
+
+    fn expression(&mut self, min: u8) -> Result<Value, String> {
+        let mut left = self.unary()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            let Some(prec) = precedence(op) else { break };
+            if prec < min {
+                break;
+            }
+            self.pos += 1;
+            left = match op {
+                "?" => {
+                    let condition = self.value(&left)? != 0;
+                    let skip = self.skip;
+                    self.skip = skip || !condition;
+                    let then = self.expression(1)?;
+                    self.expect(":")?;
+                    self.skip = skip || condition;
+                    let otherwise = self.expression(3)?;
+                    self.skip = skip;
+                    Value::Number(if condition { self.value(&then)? } else { self.value(&otherwise)? })
+                },
+                "&&" | "||" => {
+                    let left = self.value(&left)? != 0;
+                    // The right side is only evaluated when it decides the result
+                    let decided = if op == "&&" { !left } else { left };
+                    let skip = self.skip;
+                    self.skip = skip || decided;
+                    let right = self.expression(prec + 1)?;
+                    let right = self.value(&right)? != 0;
+                    self.skip = skip;
+                    Value::Number(if decided { left as i64 } else { right as i64 })
+                },
+                _ if prec == 2 => {
+                    // Assignments are right associative
+                    let right = self.expression(prec)?;
+                    let right = self.value(&right)?;
+                    let value = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                        Some(op) => self.binary(op, self.value(&left)?, right)?,
+                        None => right,
+                    };
+                    Value::Number(self.assign(&left, value)?)
+                },
+                _ => {
+                    // `**` is right associative, everything else left associative
+                    let right = self.expression(if op == "**" { prec } else { prec + 1 })?;
+                    Value::Number(self.binary(op, self.value(&left)?, self.value(&right)?)?)
+                },
+            };
+        }
+        Ok(left)
+    }
+
+    fn binary(&self, op: &str, a: i64, b: i64) -> Result<i64, String> {
+        Ok(match op {
+            "," => b,
+            "|" => a | b,
+            "^" => a ^ b,
+            "&" => a & b,
+            "==" => (a == b) as i64,
+            "!=" => (a != b) as i64,
+            "<" => (a < b) as i64,
+            "<=" => (a <= b) as i64,
+            ">" => (a > b) as i64,
+            ">=" => (a >= b) as i64,
+            "<<" => a.wrapping_shl(b as u32),
+            ">>" => a.wrapping_shr(b as u32),
+            "+" => a.wrapping_add(b),
+            "-" => a.wrapping_sub(b),
+            "*" => a.wrapping_mul(b),
+            "/" | "%" if b == 0 => {
+                if self.skip {
+                    return Ok(0);
+                }
+                return Err("division by 0".to_owned());
+            },
+            "/" => a.wrapping_div(b),
+            "%" => a.wrapping_rem(b),
+            "**" => {
+                if b < 0 {
+                    return Err("exponent less than 0".to_owned());
+                }
+                a.wrapping_pow(b.min(u32::MAX as i64) as u32)
+            },
+            _ => return Err(format!("syntax error: unexpected `{op}'")),
+        })
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Value::Number(n)),
+            Some(Token::Name(name)) => {
+                // Postfix increment and decrement
+                if let Some(Token::Op(op @ ("++" | "--"))) = self.peek() {
+                    let op = *op;
+                    self.pos += 1;
+                    let target = Value::Variable(name);
+                    let value = self.value(&target)?;
+                    self.assign(&target, if op == "++" { value + 1 } else { value - 1 })?;
+                    return Ok(Value::Number(value));
+                }
+                Ok(Value::Variable(name))
+            },
+            Some(Token::Op("(")) => {
+                let value = self.expression(1)?;
+                self.expect(")")?;
+                Ok(Value::Number(self.value(&value)?))
+            },
+            Some(Token::Op(op @ ("++" | "--"))) => {
+                let target = self.unary()?;
+                let value = self.value(&target)?;
+                let value = if op == "++" { value + 1 } else { value - 1 };
+                Ok(Value::Number(self.assign(&target, value)?))
+            },
+            Some(Token::Op(op @ ("+" | "-" | "!" | "~"))) => {
+                let operand = self.unary()?;
+                let value = self.value(&operand)?;
+                Ok(Value::Number(match op {
+                    "+" => value,
+                    "-" => value.wrapping_neg(),
+                    "!" => (value == 0) as i64,
+                    _ => !value,
+                }))
+            },
+            Some(Token::Op(op)) => Err(format!("syntax error: unexpected `{op}'")),
+            None => Err("syntax error: operand expected".to_owned()),
+        }
+    }
+}
+
+/// Evaluate an expression, an empty expression is 0
+pub fn eval(expr: &str) -> Result<i64, String> {
+    eval_nested(expr, 0)
+}
+
+fn eval_nested(expr: &str, depth: usize) -> Result<i64, String> {
+    if depth > MAX_DEPTH {
+        return Err("expression recursion level exceeded".to_owned());
+    }
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut evaluator = Evaluator { tokens, pos: 0, skip: false, depth };
+    let value = evaluator.expression(1)?;
+    if let Some(token) = evaluator.peek() {
+        return Err(format!("syntax error: unexpected {}", match token {
+            Token::Number(n) => n.to_string(),
+            Token::Name(name) => name.clone(),
+            Token::Op(op) => op.to_string(),
+        }));
+    }
+    evaluator.value(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3));
+        assert_eq!(eval("1 < 2 && 2 < 1 || 3"), Ok(1));
+        assert_eq!(eval("0 ? 1 : 2 ? 3 : 4"), Ok(3));
+        assert_eq!(eval("-2 ** 2"), Ok(4));
+        assert_eq!(eval("!0 + ~0"), Ok(0));
+        assert_eq!(eval("0x1f + 010"), Ok(39));
+        assert_eq!(eval(""), Ok(0));
+    }
+
+    #[test]
+    fn overflow_wraps() {
+        assert_eq!(eval("9223372036854775807 + 1"), Ok(i64::MIN));
+        assert_eq!(eval("-9223372036854775807 - 2"), Ok(i64::MAX));
+        assert_eq!(eval("4611686018427387904 * 2"), Ok(i64::MIN));
+        assert_eq!(eval("(-9223372036854775807 - 1) / -1"), Ok(i64::MIN));
+        assert_eq!(eval("(-9223372036854775807 - 1) % -1"), Ok(0));
+        assert_eq!(eval("2 ** 64"), Ok(0));
+        assert!(eval("9223372036854775808").is_err());
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(eval("1 / 0"), Err("division by 0".to_owned()));
+        assert_eq!(eval("1 % 0"), Err("division by 0".to_owned()));
+        // Not an error in a branch that isn't evaluated
+        assert_eq!(eval("0 && 1 / 0"), Ok(0));
+        assert_eq!(eval("1 ? 2 : 1 / 0"), Ok(2));
+        assert!(eval("2 ** -1").is_err());
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(eval("arith_test_a = 5, arith_test_a += 2, arith_test_a"), Ok(7));
+        assert_eq!(eval("arith_test_a++ + ++arith_test_a"), Ok(16));
+        assert_eq!(vars::get("arith_test_a").as_deref(), Some("9"));
+        // Skipped branches don't assign
+        assert_eq!(eval("0 && (arith_test_a = 100)"), Ok(0));
+        assert_eq!(vars::get("arith_test_a").as_deref(), Some("9"));
+        // A variable holding an expression is evaluated, unset ones are 0
+        vars::set("arith_test_b", "arith_test_a * 2");
+        assert_eq!(eval("arith_test_b + arith_test_unset"), Ok(18));
+        vars::set("arith_test_c", "arith_test_c");
+        assert!(eval("arith_test_c").is_err());
+        assert!(eval("1 = 2").is_err());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        for expr in ["1 +", "(1", "1 2", "* 3", "1 ? 2", "09"] {
+            assert!(eval(expr).is_err(), "{expr}");
+        }
+    }
+}
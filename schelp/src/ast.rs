@@ -0,0 +1,399 @@
+use std::fmt;
+use crate::lexer::is_name_char;
+use crate::redirect::RedirectKind;
+
+/// A parameter expansion like `$NAME` or `${NAME}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub braced: bool,
+    /// The operator of a braced expansion like `${NAME:-word}`
+    pub op: Option<ParamOp>,
+}
+
+/// With `colon` the operators also treat an empty value as unset
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamOp {
+    /// `${#NAME}`
+    Length,
+    /// `${NAME:-word}`
+    Default { word: Word, colon: bool },
+    /// `${NAME:=word}`
+    Assign { word: Word, colon: bool },
+    /// `${NAME:?message}`
+    Error { word: Word, colon: bool },
+    /// `${NAME:+word}`
+    Alternative { word: Word, colon: bool },
+    /// `${NAME#pattern}` and `${NAME##pattern}`
+    RemovePrefix { pattern: Word, longest: bool },
+    /// `${NAME%pattern}` and `${NAME%%pattern}`
+    RemoveSuffix { pattern: Word, longest: bool },
+}
+
+impl Param {
+    pub fn new(name: &str) -> Param {
+        Param { name: name.to_owned(), braced: false, op: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    /// Unquoted text
+    Literal(String),
+    /// Text in single quotes or escaped with a backslash
+    Quoted(String),
+    /// Text in double quotes, expansions inside are not field split
+    DoubleQuoted(Vec<WordPart>),
+    Param(Param),
+    /// `$(list)` or a backtick substitution
+    CommandSub(List),
+    /// `$((expression))`, the expression can contain parameters
+    Arith(Vec<WordPart>),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+impl Word {
+    pub fn literal(text: &str) -> Word {
+        Word { parts: vec![WordPart::Literal(text.to_owned())] }
+    }
+
+    /// The text of a word that consists of nothing but unquoted text.
+    /// Used to recognize reserved words and aliases.
+    pub fn as_literal(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(text)] => Some(text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirection {
+    pub fd: i32,
+    pub kind: RedirectKind,
+    /// The body for here-documents
+    pub target: Word,
+    /// The delimiter of a here-document as written, `-EOF` for `<<-EOF`
+    pub delimiter: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseArm {
+    pub patterns: Vec<Word>,
+    pub body: List,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompoundCommand {
+    /// `{ list; }`
+    BraceGroup(List),
+    /// `( list )`
+    Subshell(List),
+    If {
+        /// The `if` and `elif` conditions with their bodies
+        branches: Vec<(List, List)>,
+        otherwise: Option<List>,
+    },
+    /// `while` and `until` loops
+    While {
+        condition: List,
+        body: List,
+        until: bool,
+    },
+    For {
+        var: String,
+        /// None when there is no `in`, which loops over the positional parameters
+        words: Option<Vec<Word>>,
+        body: List,
+    },
+    Case {
+        word: Word,
+        arms: Vec<CaseArm>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirection>),
+    /// `name() body`
+    Function(String, Box<Command>),
+}
+
+/// Commands connected by `|`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    /// Prefixed with `time`, which reports how long it ran
+    pub time: Option<TimeFormat>,
+    /// Prefixed with `!`, which inverts the status
+    pub negated: bool,
+    /// Empty for a bare `time`
+    pub commands: Vec<Command>,
+}
+
+/// How `time` reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    /// Formatted by TIMEFORMAT
+    Default,
+    /// `time -p`, the format POSIX requires
+    Posix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+}
+
+/// Pipelines connected by `&&` and `||`, displayed without the `&`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    /// Terminated by `&`
+    pub background: bool,
+}
+
+/// A sequence of commands separated by `;`, `&` or newlines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct List {
+    pub items: Vec<AndOr>,
+}
+
+// The Display implementations turn the tree back into shell syntax,
+// used to show the commands of jobs
+
+fn write_parts(f: &mut fmt::Formatter, parts: &[WordPart], quoted: bool) -> fmt::Result {
+    for (i, part) in parts.iter().enumerate() {
+        match part {
+            WordPart::Literal(text) if quoted => {
+                for c in text.chars() {
+                    if matches!(c, '"' | '\\' | '$' | '`') {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+            },
+            WordPart::Literal(text) => write!(f, "{text}")?,
+            WordPart::Quoted(text) => write!(f, "'{}'", text.replace('\'', "'\\''"))?,
+            WordPart::DoubleQuoted(parts) => {
+                write!(f, "\"")?;
+                write_parts(f, parts, true)?;
+                write!(f, "\"")?;
+            },
+            WordPart::Param(param) => {
+                // Braces are needed when the next character would be part of the name
+                let next_is_name = match parts.get(i + 1) {
+                    Some(WordPart::Literal(text)) => text.starts_with(is_name_char),
+                    _ => false,
+                };
+                match &param.op {
+                    Some(op) => write_param_op(f, &param.name, op)?,
+                    None if param.braced || next_is_name => write!(f, "${{{}}}", param.name)?,
+                    None => write!(f, "${}", param.name)?,
+                }
+            },
+            WordPart::CommandSub(list) => write!(f, "$({list})")?,
+            WordPart::Arith(parts) => {
+                write!(f, "$((")?;
+                write_parts(f, parts, false)?;
+                write!(f, "))")?;
+            },
+        }
+    }
+    Ok(())
+}
+
+fn write_param_op(f: &mut fmt::Formatter, name: &str, op: &ParamOp) -> fmt::Result {
+    let colon = |colon: bool| if colon { ":" } else { "" };
+    match op {
+        ParamOp::Length => write!(f, "${{#{name}}}"),
+        ParamOp::Default { word, colon: c } => write!(f, "${{{name}{}-{word}}}", colon(*c)),
+        ParamOp::Assign { word, colon: c } => write!(f, "${{{name}{}={word}}}", colon(*c)),
+        ParamOp::Error { word, colon: c } => write!(f, "${{{name}{}?{word}}}", colon(*c)),
+        ParamOp::Alternative { word, colon: c } => write!(f, "${{{name}{}+{word}}}", colon(*c)),
+        ParamOp::RemovePrefix { pattern, longest } => write!(f, "${{{name}{}{pattern}}}", if *longest { "##" } else { "#" }),
+        ParamOp::RemoveSuffix { pattern, longest } => write!(f, "${{{name}{}{pattern}}}", if *longest { "%%" } else { "%" }),
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_parts(f, &self.parts, false)
+    }
+}
+
+impl fmt::Display for Redirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (op, default_fd) = match self.kind {
+            RedirectKind::Read => ("<", 0),
+            RedirectKind::Write => (">", 1),
+            RedirectKind::Append => (">>", 1),
+            RedirectKind::ReadWrite => ("<>", 0),
+            RedirectKind::Dup if self.fd == 0 => ("<&", 0),
+            RedirectKind::Dup => (">&", 1),
+            RedirectKind::HereDoc => ("<<", 0),
+            RedirectKind::HereString => ("<<<", 0),
+        };
+        if self.fd != default_fd {
+            write!(f, "{}", self.fd)?;
+        }
+        match &self.delimiter {
+            Some(delimiter) => write!(f, "{op}{delimiter}"),
+            None => write!(f, "{op}{}", self.target),
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words: Vec<String> = self.assignments.iter()
+            .map(|a| format!("{}={}", a.name, a.value))
+            .collect();
+        words.extend(self.words.iter().map(|w| w.to_string()));
+        words.extend(self.redirects.iter().map(|r| r.to_string()));
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Write a list as the body of a compound command, every command is terminated
+fn write_body(f: &mut fmt::Formatter, list: &List) -> fmt::Result {
+    for item in &list.items {
+        write!(f, " {item}{}", if item.background { " &" } else { ";" })?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for CompoundCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompoundCommand::BraceGroup(list) => {
+                write!(f, "{{")?;
+                write_body(f, list)?;
+                write!(f, " }}")
+            },
+            CompoundCommand::Subshell(list) => write!(f, "({list})"),
+            CompoundCommand::If { branches, otherwise } => {
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    write!(f, "{}", if i == 0 { "if" } else { " elif" })?;
+                    write_body(f, condition)?;
+                    write!(f, " then")?;
+                    write_body(f, body)?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, " else")?;
+                    write_body(f, otherwise)?;
+                }
+                write!(f, " fi")
+            },
+            CompoundCommand::While { condition, body, until } => {
+                write!(f, "{}", if *until { "until" } else { "while" })?;
+                write_body(f, condition)?;
+                write!(f, " do")?;
+                write_body(f, body)?;
+                write!(f, " done")
+            },
+            CompoundCommand::For { var, words, body } => {
+                write!(f, "for {var}")?;
+                if let Some(words) = words {
+                    write!(f, " in")?;
+                    for word in words {
+                        write!(f, " {word}")?;
+                    }
+                }
+                write!(f, "; do")?;
+                write_body(f, body)?;
+                write!(f, " done")
+            },
+            CompoundCommand::Case { word, arms } => {
+                write!(f, "case {word} in")?;
+                for arm in arms {
+                    let patterns: Vec<String> = arm.patterns.iter().map(|p| p.to_string()).collect();
+                    write!(f, " {})", patterns.join(" | "))?;
+                    write_body(f, &arm.body)?;
+                    write!(f, ";")?;
+                }
+                write!(f, " esac")
+            },
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Simple(simple) => write!(f, "{simple}"),
+            Command::Compound(compound, redirects) => {
+                write!(f, "{compound}")?;
+                for redirect in redirects {
+                    write!(f, " {redirect}")?;
+                }
+                Ok(())
+            },
+            Command::Function(name, body) => write!(f, "{name}() {body}"),
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let commands: Vec<String> = self.commands.iter().map(|c| c.to_string()).collect();
+        match self.time {
+            Some(TimeFormat::Default) => write!(f, "time ")?,
+            Some(TimeFormat::Posix) => write!(f, "time -p ")?,
+            None => (),
+        }
+        if self.negated {
+            write!(f, "! ")?;
+        }
+        write!(f, "{}", commands.join(" | "))
+    }
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (connector, pipeline) in &self.rest {
+            match connector {
+                Connector::And => write!(f, " && {pipeline}")?,
+                Connector::Or => write!(f, " || {pipeline}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", if self.items[i - 1].background { " " } else { "; " })?;
+            }
+            write!(f, "{item}")?;
+            if item.background {
+                write!(f, " &")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,868 @@
+//! Build_ins that are too long to live in [crate::build_in]
+
+use std::io::{self, Write};
+use crate::exec::{self, Command};
+use crate::signal::Signal;
+use crate::{jobs, lexer, parser, trap, vars, ALIASES, BUILD_INS};
+
+/// Write the output of a build_in, a closed pipe is reported instead of panicking
+fn output(cmd: &str, bytes: &[u8]) -> i32 {
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|_| stdout.flush()) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{cmd}: write error: {e}");
+            1
+        },
+    }
+}
+
+/// Interpret the backslash escape whose backslash is right before chars[i].
+/// Octal escapes are `\0nnn` when zero_octal is set, `\nnn` otherwise.
+/// Returns the index after the escape, None for `\c` which ends the output.
+fn escape(chars: &[char], mut i: usize, zero_octal: bool, out: &mut Vec<u8>) -> Option<usize> {
+    let Some(&c) = chars.get(i) else {
+        out.push(b'\\');
+        return Some(i);
+    };
+    i += 1;
+    let byte = match c {
+        'a' => 7,
+        'b' => 8,
+        'e' | 'E' => 0x1b,
+        'f' => 12,
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'v' => 11,
+        '\\' => b'\\',
+        'c' => return None,
+        '0'..='7' if c == '0' || !zero_octal => {
+            // The leading 0 doesn't count towards the three digits
+            let (start, max) = if zero_octal { (i, 3) } else { (i - 1, 3) };
+            let len = chars[start..].iter().take(max).take_while(|c| c.is_digit(8)).count();
+            let digits: String = chars[start..start + len].iter().collect();
+            i = start + len;
+            u32::from_str_radix(&digits, 8).unwrap_or(0) as u8
+        },
+        'x' => {
+            let len = chars[i..].iter().take(2).take_while(|c| c.is_ascii_hexdigit()).count();
+            if len == 0 {
+                out.extend_from_slice(b"\\x");
+                return Some(i);
+            }
+            let digits: String = chars[i..i + len].iter().collect();
+            i += len;
+            u8::from_str_radix(&digits, 16).unwrap_or(0)
+        },
+        c => {
+            out.push(b'\\');
+            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            return Some(i);
+        },
+    };
+    out.push(byte);
+    Some(i)
+}
+
+/// Interpret all escapes in a string, returns false when `\c` ended it early
+fn unescape(text: &str, zero_octal: bool, out: &mut Vec<u8>) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            match escape(&chars, i + 1, zero_octal, out) {
+                Some(next) => i = next,
+                None => return false,
+            }
+        } else {
+            out.extend_from_slice(chars[i].encode_utf8(&mut [0; 4]).as_bytes());
+            i += 1;
+        }
+    }
+    true
+}
+
+/// The echo build_in, `-n` leaves out the newline and `-e` interprets escapes
+pub fn echo(args: &[String]) -> i32 {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut args = args;
+    while let Some(arg) = args.first()
+        && let Some(flags) = arg.strip_prefix('-')
+        && !flags.is_empty()
+        && flags.chars().all(|c| matches!(c, 'n' | 'e' | 'E')) {
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        args = &args[1..];
+    }
+    let text = args.join(" ");
+    let mut out = vec![];
+    if escapes {
+        newline &= unescape(&text, true, &mut out);
+    } else {
+        out.extend_from_slice(text.as_bytes());
+    }
+    if newline {
+        out.push(b'\n');
+    }
+    output("echo", &out)
+}
+
+/// A conversion like `%-10.3s` of printf
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pad to the width, zeros go after the sign or `0x` prefix
+    fn pad(&self, text: String, numeric: bool) -> String {
+        let len = text.chars().count();
+        if len >= self.width {
+            return text;
+        }
+        let fill = self.width - len;
+        if self.left {
+            return format!("{text}{}", " ".repeat(fill));
+        }
+        if self.zero && numeric {
+            let prefix = text.chars().take_while(|c| matches!(c, '+' | '-' | ' ')).count()
+                + if text.contains("0x") || text.contains("0X") { 2 } else { 0 };
+            return format!("{}{}{}", &text[..prefix], "0".repeat(fill), &text[prefix..]);
+        }
+        format!("{}{text}", " ".repeat(fill))
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        match negative {
+            true => "-",
+            false if self.plus => "+",
+            false if self.space => " ",
+            false => "",
+        }
+    }
+
+    /// Apply the precision to the digits of an integer, the minimum number of digits
+    fn digits(&self, digits: String) -> String {
+        match self.precision {
+            Some(0) if digits == "0" => String::new(),
+            Some(precision) if digits.len() < precision => "0".repeat(precision - digits.len()) + &digits,
+            _ => digits,
+        }
+    }
+}
+
+/// Parse a printf argument as a number, `'c` is the value of the character c
+fn number(arg: &str) -> Result<i64, String> {
+    if let Some(c) = arg.strip_prefix(['\'', '"']) {
+        return Ok(c.chars().next().map_or(0, |c| c as i64));
+    }
+    let trimmed = arg.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let value = if digits.is_empty() && arg.is_empty() {
+        Ok(0)
+    } else if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && let Some(octal) = digits.strip_prefix('0') {
+        i64::from_str_radix(octal, 8)
+    } else {
+        digits.parse()
+    };
+    value.map(|v| if negative { -v } else { v }).map_err(|_| format!("{arg}: invalid number"))
+}
+
+/// Format a float in the exponent notation of C, like 1.500000e+00
+fn exponent(value: f64, precision: usize, upper: bool) -> String {
+    let text = format!("{value:.precision$e}");
+    let (mantissa, exp) = text.split_once('e').unwrap_or((&text, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    let text = format!("{mantissa}e{}{:02}", if exp < 0 { '-' } else { '+' }, exp.abs());
+    if upper { text.to_uppercase() } else { text }
+}
+
+/// Format a float like %g, the shorter of %f and %e without trailing zeros
+fn general(value: f64, precision: usize, alternate: bool, upper: bool) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 {
+        return "0".to_owned();
+    }
+    let exp = value.abs().log10().floor() as i32;
+    let trim = |text: String| match alternate || !text.contains('.') {
+        true => text,
+        false => text.trim_end_matches('0').trim_end_matches('.').to_owned(),
+    };
+    if exp < -4 || exp >= precision as i32 {
+        let text = exponent(value, precision - 1, upper);
+        let (mantissa, exp) = text.split_once(['e', 'E']).unwrap();
+        format!("{}{}{exp}", trim(mantissa.to_owned()), if upper { 'E' } else { 'e' })
+    } else {
+        trim(format!("{value:.*}", (precision as i32 - 1 - exp).max(0) as usize))
+    }
+}
+
+/// The printf build_in. The format is reused until all arguments are consumed.
+pub fn printf(args: &[String]) -> i32 {
+    let Some((format, args)) = args.split_first() else {
+        eprintln!("printf: usage: printf format [arguments]");
+        return 2;
+    };
+    let format: Vec<char> = format.chars().collect();
+    let mut args = args.iter();
+    let mut out = vec![];
+    let mut status = 0;
+
+    'outer: loop {
+        let remaining = args.len();
+        let mut i = 0;
+        while i < format.len() {
+            let c = format[i];
+            if c == '\\' {
+                match escape(&format, i + 1, false, &mut out) {
+                    Some(next) => i = next,
+                    None => break 'outer,
+                }
+                continue;
+            }
+            if c != '%' {
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                i += 1;
+                continue;
+            }
+            i += 1;
+            if format.get(i) == Some(&'%') {
+                out.push(b'%');
+                i += 1;
+                continue;
+            }
+
+            let mut spec = Spec::default();
+            while let Some(&flag) = format.get(i) {
+                match flag {
+                    '-' => spec.left = true,
+                    '0' => spec.zero = true,
+                    '+' => spec.plus = true,
+                    ' ' => spec.space = true,
+                    '#' => spec.alternate = true,
+                    _ => break,
+                }
+                i += 1;
+            }
+            // `*` takes the width or precision from the arguments
+            let count = |i: &mut usize, args: &mut std::slice::Iter<String>| -> Result<usize, String> {
+                if format.get(*i) == Some(&'*') {
+                    *i += 1;
+                    return number(args.next().map_or("", |a| a.as_str())).map(|n| n.max(0) as usize);
+                }
+                let len = format[*i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let digits: String = format[*i..*i + len].iter().collect();
+                *i += len;
+                Ok(digits.parse().unwrap_or(0))
+            };
+            let width = count(&mut i, &mut args);
+            let precision = match format.get(i) {
+                Some('.') => {
+                    i += 1;
+                    Some(count(&mut i, &mut args))
+                },
+                _ => None,
+            };
+            match (width, precision.transpose()) {
+                (Ok(width), Ok(precision)) => {
+                    spec.width = width;
+                    spec.precision = precision;
+                },
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("printf: {e}");
+                    status = 1;
+                },
+            }
+
+            let Some(&conversion) = format.get(i) else {
+                eprintln!("printf: %: missing format character");
+                status = 1;
+                break 'outer;
+            };
+            i += 1;
+            let arg = args.next().map_or("", |a| a.as_str());
+            let mut numeric = |arg: &str| number(arg).unwrap_or_else(|e| {
+                eprintln!("printf: {e}");
+                status = 1;
+                0
+            });
+            let text = match conversion {
+                's' => {
+                    let text: String = match spec.precision {
+                        Some(precision) => arg.chars().take(precision).collect(),
+                        None => arg.to_owned(),
+                    };
+                    spec.pad(text, false)
+                },
+                'b' => {
+                    let mut bytes = vec![];
+                    let finished = unescape(arg, true, &mut bytes);
+                    let text = String::from_utf8_lossy(&bytes).into_owned();
+                    out.extend_from_slice(spec.pad(text, false).as_bytes());
+                    if !finished {
+                        break 'outer;
+                    }
+                    continue;
+                },
+                'c' => spec.pad(arg.chars().next().map(String::from).unwrap_or_default(), false),
+                'd' | 'i' => {
+                    let value = numeric(arg);
+                    let digits = spec.digits(value.unsigned_abs().to_string());
+                    spec.pad(format!("{}{digits}", spec.sign(value < 0)), spec.precision.is_none())
+                },
+                'u' | 'o' | 'x' | 'X' => {
+                    let value = numeric(arg) as u64;
+                    let (digits, prefix) = match conversion {
+                        'u' => (value.to_string(), ""),
+                        'o' => (format!("{value:o}"), "0"),
+                        'x' => (format!("{value:x}"), "0x"),
+                        _ => (format!("{value:X}"), "0X"),
+                    };
+                    let digits = spec.digits(digits);
+                    let prefix = if spec.alternate && value != 0 && !digits.starts_with('0') { prefix } else { "" };
+                    spec.pad(format!("{prefix}{digits}"), spec.precision.is_none())
+                },
+                'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                    let value: f64 = match arg.trim() {
+                        "" => 0.0,
+                        text => text.parse().unwrap_or_else(|_| {
+                            eprintln!("printf: {arg}: invalid number");
+                            status = 1;
+                            0.0
+                        }),
+                    };
+                    let precision = spec.precision.unwrap_or(6);
+                    let text = match conversion {
+                        'f' | 'F' => format!("{:.precision$}", value.abs()),
+                        'e' | 'E' => exponent(value.abs(), precision, conversion == 'E'),
+                        _ => general(value.abs(), precision, spec.alternate, conversion == 'G'),
+                    };
+                    spec.pad(format!("{}{text}", spec.sign(value.is_sign_negative() && value != 0.0)), true)
+                },
+                c => {
+                    eprintln!("printf: %{c}: invalid directive");
+                    status = 1;
+                    break 'outer;
+                },
+            };
+            out.extend_from_slice(text.as_bytes());
+        }
+        // Stop when the format consumed nothing, or everything
+        if args.len() == 0 || args.len() == remaining {
+            break;
+        }
+    }
+    match output("printf", &out) {
+        0 => status,
+        code => code,
+    }
+}
+
+/// Read one line from stdin a byte at a time, so nothing after it is consumed.
+/// Returns None on EOF before any byte, an Interrupted error on ctrl-C.
+fn read_raw_line() -> io::Result<Option<Vec<u8>>> {
+    let mut line = vec![];
+    loop {
+        let mut byte = 0u8;
+        let r = unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        match r {
+            0 if line.is_empty() => return Ok(None),
+            0 => return Ok(Some(line)),
+            1 if byte == b'\n' => return Ok(Some(line)),
+            1 => line.push(byte),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted && !trap::pending(libc::SIGINT) {
+                    continue;
+                }
+                return Err(e);
+            },
+        }
+    }
+}
+
+/// The read build_in. The line is split on IFS into the named variables,
+/// the last one gets the rest of the line. Without names the line goes into REPLY.
+pub fn read(args: &[String]) -> i32 {
+    let mut raw = false;
+    let mut prompt = None;
+    let mut args = args.iter();
+    let mut names = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" => raw = true,
+            "-p" => match args.next() {
+                Some(text) => prompt = Some(text.clone()),
+                None => {
+                    eprintln!("read: -p: option requires an argument");
+                    return 2;
+                },
+            },
+            "--" => {
+                names.extend(args.cloned());
+                break;
+            },
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                eprintln!("read: {flag}: invalid option");
+                return 2;
+            },
+            name => {
+                names.push(name.to_owned());
+                names.extend(args.cloned());
+                break;
+            },
+        }
+    }
+    if let Some(name) = names.iter().find(|name| !lexer::is_name(name)) {
+        eprintln!("read: `{name}': not a valid identifier");
+        return 1;
+    }
+
+    // The prompt is only shown when reading from a terminal
+    if let Some(prompt) = prompt && unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+        eprint!("{prompt}");
+    }
+
+    // Characters of the line and whether they were escaped with a backslash
+    let mut line: Vec<(char, bool)> = vec![];
+    let mut status = 0;
+    loop {
+        let bytes = match read_raw_line() {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => {
+                status = 1;
+                break;
+            },
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return 130,
+            Err(e) => {
+                eprintln!("read: {e}");
+                return 1;
+            },
+        };
+        let text = String::from_utf8_lossy(&bytes);
+        let mut chars = text.chars();
+        let mut continued = false;
+        while let Some(c) = chars.next() {
+            if c == '\\' && !raw {
+                match chars.next() {
+                    Some(c) => line.push((c, true)),
+                    // A backslash at the end continues the line
+                    None => continued = true,
+                }
+            } else {
+                line.push((c, false));
+            }
+        }
+        if !continued {
+            break;
+        }
+    }
+
+    if names.is_empty() {
+        vars::set("REPLY", &line.iter().map(|(c, _)| c).collect::<String>());
+        return status;
+    }
+    let ifs = vars::get("IFS").unwrap_or(" \t\n".to_owned());
+    let is_delimiter = |(c, escaped): &(char, bool)| !escaped && ifs.contains(*c);
+    let is_whitespace = |item: &(char, bool)| is_delimiter(item) && item.0.is_whitespace();
+
+    let mut rest = line.as_slice();
+    while let [first, tail @ ..] = rest && is_whitespace(first) {
+        rest = tail;
+    }
+    for (i, name) in names.iter().enumerate() {
+        if i == names.len() - 1 {
+            // The last variable gets the rest of the line without trailing IFS whitespace
+            while let [init @ .., last] = rest && is_whitespace(last) {
+                rest = init;
+            }
+            vars::set(name, &rest.iter().map(|(c, _)| c).collect::<String>());
+            break;
+        }
+        let len = rest.iter().take_while(|item| !is_delimiter(item)).count();
+        vars::set(name, &rest[..len].iter().map(|(c, _)| c).collect::<String>());
+        rest = &rest[len..];
+        // A delimiter is IFS whitespace around at most one other IFS character
+        while let [first, tail @ ..] = rest && is_whitespace(first) {
+            rest = tail;
+        }
+        if let [first, tail @ ..] = rest && is_delimiter(first) {
+            rest = tail;
+            while let [first, tail @ ..] = rest && is_whitespace(first) {
+                rest = tail;
+            }
+        }
+    }
+    status
+}
+
+/// What a command name refers to
+enum Kind {
+    Alias(String),
+    Keyword,
+    Function(String),
+    BuildIn,
+    File(String),
+}
+
+/// Look a name up the same way the shell does when running it
+fn kind(name: &str) -> Option<Kind> {
+    if let Some(text) = ALIASES.lock().unwrap().get(name) {
+        return Some(Kind::Alias(text.clone()));
+    }
+    if parser::RESERVED.contains(&name) {
+        return Some(Kind::Keyword);
+    }
+    if let Some(body) = exec::function(name) {
+        return Some(Kind::Function(body.to_string()));
+    }
+    if BUILD_INS.contains(&name) {
+        return Some(Kind::BuildIn);
+    }
+    exec::find_command(name).map(Kind::File)
+}
+
+/// The type build_in, `-t` prints a single word for the kind
+pub fn describe(args: &[String]) -> i32 {
+    let (short, names) = match args.first() {
+        Some(flag) if flag == "-t" => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let mut status = 0;
+    for name in names {
+        let Some(kind) = kind(name) else {
+            if !short {
+                eprintln!("type: {name}: not found");
+            }
+            status = 1;
+            continue;
+        };
+        let (word, text) = match kind {
+            Kind::Alias(text) => ("alias", format!("{name} is aliased to `{text}'")),
+            Kind::Keyword => ("keyword", format!("{name} is a shell keyword")),
+            Kind::Function(body) => ("function", format!("{name} is a function\n{name}() {body}")),
+            Kind::BuildIn => ("builtin", format!("{name} is a shell builtin")),
+            Kind::File(path) => ("file", format!("{name} is {path}")),
+        };
+        println!("{}", if short { word } else { &text });
+    }
+    status
+}
+
+/// The command build_in. Runs a command while skipping functions,
+/// `-v` prints how a name would be run and `-V` describes it like type.
+pub fn command(args: &[String]) -> Option<i32> {
+    match args.first().map(|a| a.as_str()) {
+        Some("-v") => {
+            let mut status = 0;
+            for name in &args[1..] {
+                match kind(name) {
+                    Some(Kind::Alias(text)) => println!("alias {name}='{text}'"),
+                    Some(Kind::File(path)) => println!("{path}"),
+                    Some(_) => println!("{name}"),
+                    None => status = 1,
+                }
+            }
+            Some(status)
+        },
+        Some("-V") => Some(describe(&args[1..])),
+        Some(cmd) => {
+            let command = Command {
+                assignments: vec![],
+                cmd: cmd.to_owned(),
+                args: args[1..].to_vec(),
+                redirects: vec![],
+            };
+            exec::run_command(command).or(Some(127))
+        },
+        None => Some(0),
+    }
+}
+
+/// Permission letters for the bits of one class, like rwx for 7
+fn permissions(bits: u32) -> String {
+    [(4, 'r'), (2, 'w'), (1, 'x')].iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, c)| *c)
+        .collect()
+}
+
+/// Apply a symbolic mode like `u=rwx,go-w` to the permissions the mask allows
+fn symbolic_mode(mode: &str, allowed: u32) -> Option<u32> {
+    let mut allowed = allowed;
+    for clause in mode.split(',') {
+        let op_pos = clause.find(['=', '+', '-'])?;
+        let (who, rest) = clause.split_at(op_pos);
+        let mut classes = 0;
+        for c in who.chars() {
+            classes |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => return None,
+            };
+        }
+        if who.is_empty() {
+            classes = 0o777;
+        }
+        let (op, perms) = rest.split_at(1);
+        let mut bits = 0;
+        for c in perms.chars() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                _ => return None,
+            };
+        }
+        let bits = bits & classes;
+        match op {
+            "=" => allowed = (allowed & !classes) | bits,
+            "+" => allowed |= bits,
+            _ => allowed &= !bits,
+        }
+    }
+    Some(allowed)
+}
+
+/// The umask build_in, `-S` shows the mask as the permissions it allows
+pub fn umask(args: &[String]) -> i32 {
+    let mask = unsafe { libc::umask(0) };
+    unsafe { libc::umask(mask) };
+    let mask = mask as u32;
+    let (symbolic, args) = match args.first() {
+        Some(flag) if flag == "-S" => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let Some(mode) = args.first() else {
+        if symbolic {
+            let allowed = !mask & 0o777;
+            println!("u={},g={},o={}",
+                permissions(allowed >> 6), permissions((allowed >> 3) & 7), permissions(allowed & 7));
+        } else {
+            println!("{mask:04o}");
+        }
+        return 0;
+    };
+    let new = match mode.chars().all(|c| c.is_digit(8)) {
+        true => u32::from_str_radix(mode, 8).ok().filter(|mask| *mask <= 0o777),
+        false => symbolic_mode(mode, !mask & 0o777).map(|allowed| !allowed & 0o777),
+    };
+    match new {
+        Some(new) => {
+            unsafe { libc::umask(new as libc::mode_t) };
+            0
+        },
+        None => {
+            eprintln!("umask: {mode}: invalid mode");
+            1
+        },
+    }
+}
+
+/// Resources of ulimit with their flag, description and the unit of their values
+const LIMITS: &[(char, i32, &str, u64)] = &[
+    ('c', libc::RLIMIT_CORE as i32, "core file size (blocks)", 1024),
+    ('d', libc::RLIMIT_DATA as i32, "data seg size (kbytes)", 1024),
+    ('f', libc::RLIMIT_FSIZE as i32, "file size (blocks)", 1024),
+    ('l', libc::RLIMIT_MEMLOCK as i32, "max locked memory (kbytes)", 1024),
+    ('n', libc::RLIMIT_NOFILE as i32, "open files", 1),
+    ('s', libc::RLIMIT_STACK as i32, "stack size (kbytes)", 1024),
+    ('t', libc::RLIMIT_CPU as i32, "cpu time (seconds)", 1),
+    ('u', libc::RLIMIT_NPROC as i32, "max user processes", 1),
+    ('v', libc::RLIMIT_AS as i32, "virtual memory (kbytes)", 1024),
+];
+
+/// The ulimit build_in, shows or sets the soft (-S) and/or hard (-H) limit of a resource
+pub fn ulimit(args: &[String]) -> i32 {
+    let (mut soft, mut hard, mut all) = (false, false, false);
+    let mut resource = LIMITS.iter().find(|(flag, ..)| *flag == 'f').unwrap();
+    let mut value = None;
+    for arg in args {
+        let Some(flags) = arg.strip_prefix('-') else {
+            value = Some(arg);
+            continue;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'S' => soft = true,
+                'H' => hard = true,
+                'a' => all = true,
+                flag => match LIMITS.iter().find(|(f, ..)| *f == flag) {
+                    Some(limit) => resource = limit,
+                    None => {
+                        eprintln!("ulimit: -{flag}: invalid option");
+                        return 2;
+                    },
+                },
+            }
+        }
+    }
+
+    let get = |resource: i32| {
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        unsafe { libc::getrlimit(resource as _, &mut limit) };
+        limit
+    };
+    let show = |limit: libc::rlimit, unit: u64| {
+        let value = if hard && !soft { limit.rlim_max } else { limit.rlim_cur };
+        match value {
+            libc::RLIM_INFINITY => "unlimited".to_owned(),
+            value => (value / unit).to_string(),
+        }
+    };
+    if all {
+        for (flag, resource, description, unit) in LIMITS {
+            println!("{description:<32}(-{flag}) {}", show(get(*resource), *unit));
+        }
+        return 0;
+    }
+    let (_, resource, _, unit) = *resource;
+    let Some(value) = value else {
+        println!("{}", show(get(resource), unit));
+        return 0;
+    };
+    let value = match value.as_str() {
+        "unlimited" => libc::RLIM_INFINITY,
+        text => match text.parse::<u64>() {
+            Ok(n) => n.saturating_mul(unit),
+            Err(_) => {
+                eprintln!("ulimit: {text}: invalid number");
+                return 1;
+            },
+        },
+    };
+    // Without -S or -H both limits are set
+    let mut limit = get(resource);
+    if soft || !hard {
+        limit.rlim_cur = value;
+    }
+    if hard || !soft {
+        limit.rlim_max = value;
+    }
+    if unsafe { libc::setrlimit(resource as _, &limit) } == -1 {
+        eprintln!("ulimit: {}", io::Error::last_os_error());
+        return 1;
+    }
+    0
+}
+
+/// Parse a signal name or number for kill, 0 only checks whether the process exists
+fn kill_signal(name: &str) -> Option<i32> {
+    match name {
+        "0" => Some(0),
+        _ => Signal::from_name(name).map(|sig| sig.number()),
+    }
+}
+
+/// The kill build_in, sends a signal to processes or jobs. `-l` lists the signal names.
+pub fn kill(args: &[String]) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut args = args;
+    match args.first().map(|a| a.as_str()) {
+        Some("-l" | "-L") => {
+            if args.len() == 1 {
+                let names: Vec<String> = Signal::all().into_iter()
+                    .map(|sig| format!("{:2}) SIG{}", sig.number(), sig.name()))
+                    .collect();
+                for row in names.chunks(5) {
+                    println!("{}", row.iter().map(|n| format!("{n:<16}")).collect::<String>().trim_end());
+                }
+                return 0;
+            }
+            let mut status = 0;
+            for arg in &args[1..] {
+                // Exit statuses of processes killed by a signal work as well
+                let sig = arg.parse::<i32>().map(|n| if n > 128 { n - 128 } else { n });
+                match sig.ok().and_then(|sig| Signal::try_from(sig).ok()) {
+                    Some(sig) => println!("{}", sig.name()),
+                    None => match Signal::from_name(arg) {
+                        Some(sig) => println!("{}", sig.number()),
+                        None => {
+                            eprintln!("kill: {arg}: invalid signal specification");
+                            status = 1;
+                        },
+                    },
+                }
+            }
+            return status;
+        },
+        Some(flag @ ("-s" | "-n")) => {
+            let Some(name) = args.get(1) else {
+                eprintln!("kill: {flag}: option requires an argument");
+                return 2;
+            };
+            match kill_signal(name) {
+                Some(sig) => signal = sig,
+                None => {
+                    eprintln!("kill: {name}: invalid signal specification");
+                    return 1;
+                },
+            }
+            args = &args[2..];
+        },
+        Some("--") => args = &args[1..],
+        Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+            match kill_signal(&arg[1..]) {
+                Some(sig) => signal = sig,
+                None => {
+                    eprintln!("kill: {}: invalid signal specification", &arg[1..]);
+                    return 1;
+                },
+            }
+            args = &args[1..];
+        },
+        _ => (),
+    }
+    if args.first().is_some_and(|a| a == "--") {
+        args = &args[1..];
+    }
+    if args.is_empty() {
+        eprintln!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]");
+        return 2;
+    }
+
+    let mut status = 0;
+    for target in args {
+        let ids = match target.starts_with('%') {
+            true => jobs::kill_ids(target),
+            false => target.parse::<i32>()
+                .map(|pid| vec![pid])
+                .map_err(|_| format!("{target}: arguments must be process or job IDs")),
+        };
+        let ids = match ids {
+            Ok(ids) => ids,
+            Err(e) => {
+                eprintln!("kill: {e}");
+                status = 1;
+                continue;
+            },
+        };
+        for id in ids {
+            if unsafe { libc::kill(id, signal) } == -1 {
+                eprintln!("kill: ({target}) - {}", io::Error::last_os_error());
+                status = 1;
+            }
+        }
+    }
+    status
+}
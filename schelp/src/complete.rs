@@ -0,0 +1,128 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use crate::{jobs, vars, ALIASES, BUILD_INS};
+
+/// Characters that separate words on the command line
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')' | '"')
+}
+
+/// Whether a word starting at `start` is in the position of a command name
+fn is_command_position(line: &[char], start: usize) -> bool {
+    let before: String = line[..start].iter().collect();
+    let before = before.trim_end();
+    before.is_empty() || before.ends_with(['|', '&', ';', '('])
+}
+
+/// Find completions for the word the cursor is at.
+/// Returns where the word starts and the candidates that can replace it.
+pub fn complete(line: &[char], cursor: usize) -> (usize, Vec<String>) {
+    let mut start = cursor;
+    while start > 0 && !is_separator(line[start - 1]) {
+        start -= 1;
+    }
+    let word: String = line[start..cursor].iter().collect();
+
+    let mut candidates = if let Some(name) = word.strip_prefix('$') {
+        vars::names().into_iter()
+            .filter(|var| var.starts_with(name))
+            .map(|var| format!("${var}"))
+            .collect()
+    } else if word.starts_with('%') {
+        jobs::ids().into_iter()
+            .map(|id| format!("%{id}"))
+            .filter(|spec| spec.starts_with(&word))
+            .collect()
+    } else if is_command_position(line, start) && !word.contains('/') {
+        commands(&word)
+    } else {
+        paths(&word)
+    };
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+/// Build_ins, aliases and executables in PATH
+fn commands(prefix: &str) -> Vec<String> {
+    let mut commands: Vec<String> = BUILD_INS.iter()
+        .filter(|b| b.starts_with(prefix))
+        .map(|b| b.to_string())
+        .collect();
+    commands.extend(ALIASES.lock().unwrap().keys().filter(|a| a.starts_with(prefix)).cloned());
+
+    let path = vars::get("PATH").unwrap_or_default();
+    for dir in path.split(':') {
+        let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else { continue };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(prefix)
+                && let Ok(metadata) = entry.path().metadata()
+                && metadata.is_file()
+                && metadata.permissions().mode() & 0o111 != 0 {
+                commands.push(name);
+            }
+        }
+    }
+    commands
+}
+
+/// Files matching a partial path, directories end with a slash
+fn paths(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let search_dir = match dir {
+        "" => ".".to_owned(),
+        _ if dir.starts_with("~/") => vars::get("HOME").unwrap_or_default() + &dir[1..],
+        _ => dir.to_owned(),
+    };
+
+    let Ok(entries) = fs::read_dir(Path::new(&search_dir)) else { return vec![] };
+    entries.flatten()
+        .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path().is_dir()))
+        // Hidden files are only completed when asked for
+        .filter(|(name, _)| name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.')))
+        .map(|(name, is_dir)| format!("{dir}{name}{}", if is_dir { "/" } else { "" }))
+        .collect()
+}
+
+/// The longest prefix all candidates share
+pub fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else { return String::new() };
+    let mut prefix: Vec<char> = first.chars().collect();
+    for candidate in &candidates[1..] {
+        let len = prefix.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(len);
+    }
+    prefix.into_iter().collect()
+}
+
+/// Format candidates in columns like ls, sorted top to bottom
+pub fn columns(candidates: &[String], width: usize) -> String {
+    // Only show the last part of paths
+    let names: Vec<&str> = candidates.iter()
+        .map(|c| match c.trim_end_matches('/').rfind('/') {
+            Some(i) => &c[i + 1..],
+            None => c.as_str(),
+        })
+        .collect();
+    let column_width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0) + 2;
+    let columns = (width / column_width).max(1);
+    let rows = names.len().div_ceil(columns);
+
+    let mut out = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for column in 0..columns {
+            if let Some(name) = names.get(column * rows + row) {
+                line += &format!("{name:<column_width$}");
+            }
+        }
+        out += line.trim_end();
+        out += "\r\n";
+    }
+    out
+}
@@ -0,0 +1,364 @@
+//! The working directory: cd, pwd and the directory stack of pushd, popd and dirs.
+//! PWD is kept logical, `cd dir/..` returns to where it started even if dir is a symlink.
+
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::Mutex;
+use crate::vars;
+
+/// The directories below the working directory, which is the top of the stack
+static STACK: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+fn physical() -> Result<String, String> {
+    std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
+/// Whether two paths are the same file
+fn same_file(a: &str, b: &str) -> bool {
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// The directory stack and the working directory of a shell while it isn't its turn, see [swap]
+#[derive(Default)]
+pub struct State {
+    stack: Vec<String>,
+    /// None for a new shell, which starts in the directory of the process
+    dir: Option<File>,
+}
+
+/// Exchange the directory stack and the working directory with those of another shell.
+/// The directory is kept open so it is the same one even if it was moved.
+pub fn swap(state: &mut State) {
+    std::mem::swap(&mut *STACK.lock().unwrap(), &mut state.stack);
+    let here = File::open(".").ok();
+    if let Some(dir) = state.dir.take() {
+        unsafe { libc::fchdir(dir.as_raw_fd()) };
+    }
+    state.dir = here;
+}
+
+/// Keep the PWD the shell was started with if it is right, so it stays logical
+pub fn init() {
+    match vars::get("PWD") {
+        Some(pwd) if pwd.starts_with('/') && same_file(&pwd, ".") => (),
+        _ => if let Ok(dir) = physical() {
+            vars::export("PWD", Some(&dir));
+        },
+    }
+}
+
+/// The logical working directory
+pub fn current() -> String {
+    match vars::get("PWD") {
+        Some(pwd) if pwd.starts_with('/') => pwd,
+        _ => physical().unwrap_or_default(),
+    }
+}
+
+/// A path with the home directory abbreviated to `~`
+pub fn abbreviate(path: &str) -> String {
+    match vars::get("HOME") {
+        Some(home) if !home.is_empty() && home != "/" && path == home => "~".to_owned(),
+        Some(home) if !home.is_empty() && home != "/"
+            && let Some(rest) = path.strip_prefix(&home)
+            && rest.starts_with('/') => format!("~{rest}"),
+        _ => path.to_owned(),
+    }
+}
+
+/// Remove `.` and `dir/..` from an absolute path without looking at the file system
+fn canonical(path: &str) -> String {
+    let mut parts = vec![];
+    for part in path.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => { parts.pop(); },
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Change the working directory and update PWD and OLDPWD.
+/// Physically PWD is whatever the directory really is, logically the path with `..` removed.
+/// When the logical path doesn't exist, like `link/..` where the link's target has another parent,
+/// the directory is changed physically instead.
+fn change_dir(dir: &str, physical_mode: bool) -> Result<(), String> {
+    let old = current();
+    let target = match dir.starts_with('/') {
+        true => dir.to_owned(),
+        false => format!("{}/{dir}", old.trim_end_matches('/')),
+    };
+    let logical = match physical_mode {
+        true => None,
+        false => Some(canonical(&target)).filter(|logical| std::env::set_current_dir(logical).is_ok()),
+    };
+    let new = match logical {
+        Some(logical) => logical,
+        None => {
+            std::env::set_current_dir(&target).map_err(|e| format!("{dir}: {e}"))?;
+            physical()?
+        },
+    };
+    vars::set("OLDPWD", &old);
+    vars::set("PWD", &new);
+    Ok(())
+}
+
+/// Split the -L and -P options from the arguments, the last one wins
+fn mode(args: &[String]) -> Result<(bool, &[String]), String> {
+    let mut physical = false;
+    let mut args = args;
+    while let Some(arg) = args.first() {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => {
+                args = &args[1..];
+                break;
+            },
+            arg if arg.starts_with('-') && arg.len() > 1 => return Err(format!("{arg}: invalid option")),
+            _ => break,
+        }
+        args = &args[1..];
+    }
+    Ok((physical, args))
+}
+
+/// The directories in CDPATH where a relative directory is looked for,
+/// empty entries are the current directory
+fn search_cdpath(dir: &str) -> Option<String> {
+    let cdpath = vars::get("CDPATH").filter(|cdpath| !cdpath.is_empty())?;
+    if dir.starts_with('/') || dir == "." || dir == ".." || dir.starts_with("./") || dir.starts_with("../") {
+        return None;
+    }
+    cdpath.split(':')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| format!("{}/{dir}", entry.trim_end_matches('/')))
+        .find(|path| Path::new(path).is_dir())
+}
+
+/// The cd build_in. `cd` goes home, `cd -` back to OLDPWD,
+/// relative directories are looked for in CDPATH.
+pub fn cd(args: &[String]) -> i32 {
+    let (physical, args) = match mode(args) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("cd: {e}");
+            return 2;
+        },
+    };
+    if args.len() > 1 {
+        eprintln!("cd: too many arguments");
+        return 1;
+    }
+    // Whether to print the new directory, which wasn't the one given
+    let mut print = false;
+    let dir = match args.first().map(|dir| dir.as_str()) {
+        None => match vars::get("HOME").filter(|home| !home.is_empty()) {
+            Some(home) => home,
+            None => {
+                eprintln!("cd: HOME not set");
+                return 1;
+            },
+        },
+        Some("-") => match vars::get("OLDPWD").filter(|old| !old.is_empty()) {
+            Some(old) => {
+                print = true;
+                old
+            },
+            None => {
+                eprintln!("cd: OLDPWD not set");
+                return 1;
+            },
+        },
+        Some(dir) => match search_cdpath(dir) {
+            Some(found) => {
+                print = true;
+                found
+            },
+            None => dir.to_owned(),
+        },
+    };
+    if let Err(e) = change_dir(&dir, physical) {
+        eprintln!("cd: {e}");
+        return 1;
+    }
+    if print {
+        println!("{}", current());
+    }
+    0
+}
+
+/// The pwd build_in, `-P` prints the directory with symlinks resolved
+pub fn pwd(args: &[String]) -> i32 {
+    let (physical_mode, _) = match mode(args) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("pwd: {e}");
+            return 2;
+        },
+    };
+    let dir = match physical_mode {
+        true => physical(),
+        // PWD can be stale when something else removed or moved the directory
+        false => Some(current()).filter(|pwd| same_file(pwd, ".")).map_or_else(physical, Ok),
+    };
+    match dir {
+        Ok(dir) => {
+            println!("{dir}");
+            0
+        },
+        Err(e) => {
+            eprintln!("pwd: {e}");
+            1
+        },
+    }
+}
+
+/// The stack with the working directory on top
+fn stack() -> Vec<String> {
+    let mut stack = vec![current()];
+    stack.extend(STACK.lock().unwrap().iter().cloned());
+    stack
+}
+
+/// Parse `+N` or `-N`, the Nth entry from the top or bottom of a stack of the given size
+fn stack_index(arg: &str, size: usize) -> Option<Result<usize, String>> {
+    let (from_top, n) = match arg.split_at_checked(1) {
+        Some(("+", n)) => (true, n),
+        Some(("-", n)) => (false, n),
+        _ => return None,
+    };
+    let n: usize = n.parse().ok()?;
+    Some(match n < size {
+        true if from_top => Ok(n),
+        true => Ok(size - 1 - n),
+        false => Err(format!("{arg}: directory stack index out of range")),
+    })
+}
+
+fn print_stack(long: bool, per_line: bool, numbered: bool) {
+    let stack: Vec<String> = stack().into_iter()
+        .map(|dir| if long { dir } else { abbreviate(&dir) })
+        .collect();
+    if numbered {
+        for (i, dir) in stack.iter().enumerate() {
+            println!("{i:2}  {dir}");
+        }
+    } else if per_line {
+        for dir in stack {
+            println!("{dir}");
+        }
+    } else {
+        println!("{}", stack.join(" "));
+    }
+}
+
+/// The dirs build_in. `-c` clears the stack, `-l` doesn't abbreviate the home directory,
+/// `-p` prints a directory per line and `-v` numbers them.
+pub fn dirs(args: &[String]) -> i32 {
+    let (mut long, mut per_line, mut numbered) = (false, false, false);
+    for arg in args {
+        if let Some(index) = stack_index(arg, STACK.lock().unwrap().len() + 1) {
+            match index {
+                Ok(index) => println!("{}", abbreviate(&stack()[index])),
+                Err(e) => {
+                    eprintln!("dirs: {e}");
+                    return 1;
+                },
+            }
+            return 0;
+        }
+        match arg.as_str() {
+            "-c" => STACK.lock().unwrap().clear(),
+            "-l" => long = true,
+            "-p" => per_line = true,
+            "-v" => numbered = true,
+            arg => {
+                eprintln!("dirs: {arg}: invalid option");
+                return 2;
+            },
+        }
+    }
+    if !args.iter().any(|arg| arg == "-c") {
+        print_stack(long, per_line, numbered);
+    }
+    0
+}
+
+/// The pushd build_in. Pushes the working directory and changes to a new one,
+/// without arguments the top two are swapped and `+N` rotates the Nth entry to the top.
+/// `-L` and `-P` change the directory like they do for cd.
+pub fn pushd(args: &[String]) -> i32 {
+    // -L and -P like cd, up to a `+N` or `-N`
+    let options = args.iter().take_while(|arg| stack_index(arg, usize::MAX).is_none()).count();
+    let (physical, rest) = match mode(&args[..options]) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("pushd: {e}");
+            return 2;
+        },
+    };
+    let args = &args[options - rest.len()..];
+    let stack = stack();
+    let new_stack: Vec<String> = match args.first() {
+        None if stack.len() < 2 => {
+            eprintln!("pushd: no other directory");
+            return 1;
+        },
+        None => [vec![stack[1].clone(), stack[0].clone()], stack[2..].to_vec()].concat(),
+        Some(arg) => match stack_index(arg, stack.len()) {
+            Some(Ok(index)) => [&stack[index..], &stack[..index]].concat(),
+            Some(Err(e)) => {
+                eprintln!("pushd: {e}");
+                return 1;
+            },
+            None => [vec![arg.clone()], stack].concat(),
+        },
+    };
+    if let Err(e) = change_dir(&new_stack[0], physical) {
+        eprintln!("pushd: {e}");
+        return 1;
+    }
+    *STACK.lock().unwrap() = new_stack[1..].to_vec();
+    print_stack(false, false, false);
+    0
+}
+
+/// The popd build_in. Removes the top of the stack and changes to the next,
+/// `+N` and `-N` remove the Nth entry instead.
+pub fn popd(args: &[String]) -> i32 {
+    let mut stack = stack();
+    if stack.len() < 2 {
+        eprintln!("popd: directory stack empty");
+        return 1;
+    }
+    let index = match args.first().map(|arg| stack_index(arg, stack.len())) {
+        None => 0,
+        Some(Some(Ok(index))) => index,
+        Some(Some(Err(e))) => {
+            eprintln!("popd: {e}");
+            return 1;
+        },
+        Some(None) => {
+            eprintln!("popd: {}: invalid argument", args[0]);
+            return 2;
+        },
+    };
+    stack.remove(index);
+    if index == 0 && let Err(e) = change_dir(&stack[0], false) {
+        eprintln!("popd: {e}");
+        return 1;
+    }
+    *STACK.lock().unwrap() = stack[1..].to_vec();
+    print_stack(false, false, false);
+    0
+}
@@ -0,0 +1,564 @@
+use std::io::{self, Write};
+use std::mem::MaybeUninit;
+use color::grey;
+use crate::{complete, highlight, history, trap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    /// ctrl + a lowercase letter
+    Ctrl(char),
+    /// alt (or escape) + a character
+    Alt(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+/// Puts the terminal in raw mode, the original mode is restored on drop
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        unsafe {
+            let mut original = MaybeUninit::uninit();
+            if libc::tcgetattr(libc::STDIN_FILENO, original.as_mut_ptr()) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let original = original.assume_init();
+            let mut raw = original;
+            // Output processing stays on so \n still moves to the start of the line
+            raw.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+            raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+            raw.c_cflag |= libc::CS8;
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original) };
+    }
+}
+
+/// Read a single byte, None on EOF or when the timeout (in ms) expires
+fn read_byte(timeout: Option<i32>) -> io::Result<Option<u8>> {
+    if let Some(timeout) = timeout {
+        let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        if unsafe { libc::poll(&mut fd, 1, timeout) } <= 0 {
+            return Ok(None);
+        }
+    }
+    loop {
+        let mut byte = 0u8;
+        let r = unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        match r {
+            0 => return Ok(None),
+            1 => return Ok(Some(byte)),
+            _ => {
+                let e = io::Error::last_os_error();
+                // Only give up when someone sent us SIGINT
+                if e.kind() == io::ErrorKind::Interrupted && !trap::pending(libc::SIGINT) {
+                    continue;
+                }
+                return Err(e);
+            },
+        }
+    }
+}
+
+/// Read a key press, None on EOF
+fn read_key() -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte(None)? else { return Ok(None) };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        1..=26 => Key::Ctrl((b'a' + byte - 1) as char),
+        0x1b => read_escape()?,
+        // Ctrl-@ and the like, a NUL byte can't be passed to a command
+        0 | 0x1c..=0x1f => Key::Unknown,
+        0..=0x7f => Key::Char(byte as char),
+        _ => {
+            // The length of a UTF-8 sequence is encoded in the first byte
+            let len = byte.leading_ones() as usize;
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                match read_byte(None)? {
+                    Some(byte) => bytes.push(byte),
+                    None => break,
+                }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        },
+    };
+    Ok(Some(key))
+}
+
+/// Parse what follows an escape byte
+fn read_escape() -> io::Result<Key> {
+    // A lone escape is not followed by anything
+    let Some(byte) = read_byte(Some(50))? else { return Ok(Key::Unknown) };
+    match byte {
+        b'[' => {
+            let mut params = String::new();
+            loop {
+                let Some(byte) = read_byte(Some(50))? else { return Ok(Key::Unknown) };
+                if (0x40..=0x7e).contains(&byte) {
+                    return Ok(match (params.as_str(), byte) {
+                        (_, b'A') => Key::Up,
+                        (_, b'B') => Key::Down,
+                        (_, b'C') => Key::Right,
+                        (_, b'D') => Key::Left,
+                        (_, b'H') => Key::Home,
+                        (_, b'F') => Key::End,
+                        ("1" | "7", b'~') => Key::Home,
+                        ("4" | "8", b'~') => Key::End,
+                        ("3", b'~') => Key::Delete,
+                        _ => Key::Unknown,
+                    });
+                }
+                params.push(byte as char);
+            }
+        },
+        b'O' => Ok(match read_byte(Some(50))? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Unknown,
+        }),
+        0x7f => Ok(Key::Alt('\x7f')),
+        _ => Ok(Key::Alt(byte as char)),
+    }
+}
+
+fn bell() {
+    print!("\x07");
+}
+
+/// Width of the terminal in columns
+fn columns() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == -1 || size.ws_col == 0 {
+        return 80;
+    }
+    size.ws_col as usize
+}
+
+/// The number of columns a string takes up, ignoring escape sequences
+pub fn width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip until the final byte of the sequence
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else if !c.is_control() {
+            width += 1;
+        }
+    }
+    width
+}
+
+struct Editor {
+    buffer: Vec<char>,
+    cursor: usize,
+    prompt: String,
+    prompt_width: usize,
+    /// Shown at the right edge of the terminal while the line leaves room for it
+    right_prompt: String,
+    /// Terminal row of the cursor relative to the first row of the prompt
+    cursor_row: usize,
+    /// Position while browsing the history, equal to the history length for the line being edited
+    history_index: usize,
+    /// The line that was being edited before browsing the history
+    saved_line: Vec<char>,
+    /// Text removed by the last kill command, inserted with ctrl-Y
+    killed: Vec<char>,
+    /// Whether the rest of a matching history entry is shown after the line
+    suggest: bool,
+}
+
+impl Editor {
+    fn new(prompt: &str, right_prompt: &str) -> Editor {
+        Editor {
+            buffer: vec![],
+            cursor: 0,
+            prompt: prompt.to_owned(),
+            prompt_width: width(prompt),
+            right_prompt: right_prompt.to_owned(),
+            cursor_row: 0,
+            history_index: history::len(),
+            saved_line: vec![],
+            killed: vec![],
+            suggest: true,
+        }
+    }
+
+    fn line(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    /// The rest of the most recent history entry starting with the line, when the cursor is at its end
+    fn suggestion(&self) -> Option<String> {
+        if !self.suggest || self.cursor < self.buffer.len() {
+            return None;
+        }
+        let line = self.line();
+        history::suggest(&line).map(|entry| entry[line.len()..].to_owned())
+    }
+
+    /// Take the suggested rest of the line, returns false if there is none
+    fn accept_suggestion(&mut self) -> bool {
+        let Some(suggestion) = self.suggestion() else { return false };
+        self.buffer.extend(suggestion.chars());
+        self.cursor = self.buffer.len();
+        true
+    }
+
+    /// Redraw the prompt and the line, placing the terminal cursor at self.cursor
+    fn render(&mut self) {
+        let cols = columns();
+        let suggestion = self.suggestion().unwrap_or_default();
+        let mut out = String::new();
+        if self.cursor_row > 0 {
+            out += &format!("\x1b[{}A", self.cursor_row);
+        }
+        out.push('\r');
+        out += &self.prompt;
+        out += &highlight::highlight(&self.line());
+        if !suggestion.is_empty() {
+            out += &grey!(suggestion);
+        }
+        // Clear whatever was left of the previous render
+        out += "\x1b[J";
+
+        let total = self.prompt_width + self.buffer.len() + suggestion.chars().count();
+        let right_width = width(&self.right_prompt);
+        if right_width > 0 && total + right_width < cols {
+            out += &format!("\r\x1b[{}C{}", cols - right_width, self.right_prompt);
+        }
+        if total > 0 && total.is_multiple_of(cols) {
+            // The terminal doesn't wrap until another character is printed
+            out += "\r\n";
+        }
+        let end_row = total / cols;
+        let position = self.prompt_width + self.cursor;
+        let (row, col) = (position / cols, position % cols);
+        if end_row > row {
+            out += &format!("\x1b[{}A", end_row - row);
+        }
+        out.push('\r');
+        if col > 0 {
+            out += &format!("\x1b[{col}C");
+        }
+        self.cursor_row = row;
+
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn set_line(&mut self, line: Vec<char>) {
+        self.buffer = line;
+        self.cursor = self.buffer.len();
+    }
+
+    fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Remove a range of the buffer and remember it for ctrl-Y
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.killed = self.buffer.drain(start..end).collect();
+            self.cursor = start;
+        }
+    }
+
+    /// Start of the word before the cursor, words are delimited by whitespace
+    fn word_start_whitespace(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.buffer[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        while i > 0 && !self.buffer[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        i
+    }
+
+    /// Start of the alphanumeric word before the cursor
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !self.buffer[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        while i > 0 && self.buffer[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        i
+    }
+
+    /// End of the alphanumeric word after the cursor
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.buffer.len() && !self.buffer[i].is_alphanumeric() {
+            i += 1;
+        }
+        while i < self.buffer.len() && self.buffer[i].is_alphanumeric() {
+            i += 1;
+        }
+        i
+    }
+
+    /// Replace the text between start and the cursor
+    fn replace(&mut self, start: usize, text: &str) {
+        self.buffer.splice(start..self.cursor, text.chars());
+        self.cursor = start + text.chars().count();
+    }
+
+    /// Complete the word at the cursor, candidates are listed when tab is pressed twice
+    fn complete(&mut self, double_tab: bool) {
+        let (start, candidates) = complete::complete(&self.buffer, self.cursor);
+        match candidates.as_slice() {
+            [] => bell(),
+            [candidate] => {
+                let space = if candidate.ends_with('/') { "" } else { " " };
+                self.replace(start, &format!("{candidate}{space}"));
+            },
+            _ => {
+                let prefix = complete::common_prefix(&candidates);
+                if prefix.chars().count() > self.cursor - start {
+                    self.replace(start, &prefix);
+                } else if double_tab {
+                    let cursor = self.cursor;
+                    self.cursor = self.buffer.len();
+                    self.render();
+                    print!("\r\n{}", complete::columns(&candidates, columns()));
+                    self.cursor = cursor;
+                    self.cursor_row = 0;
+                } else {
+                    bell();
+                }
+            },
+        }
+    }
+
+    fn history_previous(&mut self) {
+        if self.history_index == 0 {
+            return;
+        }
+        if self.history_index == history::len() {
+            self.saved_line = self.buffer.clone();
+        }
+        self.history_index -= 1;
+        if let Some(entry) = history::get(self.history_index) {
+            self.set_line(entry.chars().collect());
+        }
+    }
+
+    fn history_next(&mut self) {
+        let len = history::len();
+        if self.history_index >= len {
+            return;
+        }
+        self.history_index += 1;
+        if self.history_index == len {
+            self.set_line(self.saved_line.clone());
+        } else if let Some(entry) = history::get(self.history_index) {
+            self.set_line(entry.chars().collect());
+        }
+    }
+
+    /// Incremental search through the history with ctrl-R.
+    /// Returns the key that ended the search, None when it was cancelled.
+    fn reverse_search(&mut self) -> io::Result<Option<Key>> {
+        let original = (self.buffer.clone(), self.cursor);
+        self.suggest = false;
+        let prompt = std::mem::take(&mut self.prompt);
+        let prompt_width = self.prompt_width;
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+
+        let result = loop {
+            self.prompt = format!("({}reverse-i-search)`{query}': ", if failed { "failed " } else { "" });
+            self.prompt_width = width(&self.prompt);
+            self.render();
+
+            // Where to start searching backwards from
+            let before = match read_key()? {
+                Some(Key::Char(c)) => {
+                    query.push(c);
+                    // The current match may still match
+                    found.map(|i| i + 1).unwrap_or(history::len())
+                },
+                Some(Key::Backspace) => {
+                    query.pop();
+                    history::len()
+                },
+                Some(Key::Ctrl('r')) => found.unwrap_or(history::len()),
+                Some(Key::Ctrl('g')) | Some(Key::Ctrl('c')) | None => {
+                    (self.buffer, self.cursor) = original;
+                    break None;
+                },
+                Some(key) => break Some(key),
+            };
+
+            if query.is_empty() {
+                found = None;
+                failed = false;
+                (self.buffer, self.cursor) = original.clone();
+                continue;
+            }
+            match history::search(&query, before) {
+                Some(index) => {
+                    let entry = history::get(index).unwrap_or_default();
+                    let position = entry.find(&query).unwrap_or(0);
+                    self.buffer = entry.chars().collect();
+                    self.cursor = entry[..position].chars().count();
+                    self.history_index = index;
+                    found = Some(index);
+                    failed = false;
+                },
+                None => failed = true,
+            }
+        };
+
+        self.prompt = prompt;
+        self.prompt_width = prompt_width;
+        self.suggest = true;
+        self.render();
+        Ok(result)
+    }
+}
+
+/// Read a line with the line editor, the right prompt may be empty.
+/// Returns None on EOF, and an Interrupted error when ctrl-C was pressed.
+pub fn read_line(prompt: &str, right_prompt: &str) -> io::Result<Option<String>> {
+    let _raw = RawMode::enable()?;
+    let mut editor = Editor::new(prompt, right_prompt);
+    editor.render();
+
+    // A key that ended a reverse search and still has to be handled
+    let mut pending = None;
+    let mut last_key = None;
+    loop {
+        let key = match pending.take() {
+            Some(key) => key,
+            None => match read_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            },
+        };
+        match key {
+            Key::Tab => editor.complete(last_key == Some(Key::Tab)),
+            Key::Enter => {
+                editor.cursor = editor.buffer.len();
+                // The line is left on the screen without the suggestion
+                editor.suggest = false;
+                editor.render();
+                print!("\r\n");
+                io::stdout().flush()?;
+                return Ok(Some(editor.line()));
+            },
+            Key::Ctrl('c') => {
+                editor.cursor = editor.buffer.len();
+                editor.suggest = false;
+                editor.render();
+                print!("^C");
+                io::stdout().flush()?;
+                // Let the SIGINT trap run as if the terminal sent the signal
+                unsafe { libc::raise(libc::SIGINT) };
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            },
+            Key::Ctrl('d') => {
+                if editor.buffer.is_empty() {
+                    print!("\r\n");
+                    io::stdout().flush()?;
+                    return Ok(None);
+                }
+                if editor.cursor < editor.buffer.len() {
+                    editor.buffer.remove(editor.cursor);
+                }
+            },
+            Key::Char(c) => editor.insert(c),
+            Key::Backspace | Key::Ctrl('h') if editor.cursor > 0 => {
+                editor.cursor -= 1;
+                editor.buffer.remove(editor.cursor);
+            },
+            Key::Delete if editor.cursor < editor.buffer.len() => {
+                editor.buffer.remove(editor.cursor);
+            },
+            Key::Left | Key::Ctrl('b') => editor.cursor = editor.cursor.saturating_sub(1),
+            // At the end of the line these accept the suggestion
+            Key::Right | Key::Ctrl('f') if !editor.accept_suggestion() => {
+                editor.cursor = (editor.cursor + 1).min(editor.buffer.len());
+            },
+            Key::Home | Key::Ctrl('a') => editor.cursor = 0,
+            Key::End | Key::Ctrl('e') if !editor.accept_suggestion() => {
+                editor.cursor = editor.buffer.len();
+            },
+            Key::Alt('b') => editor.cursor = editor.word_start(),
+            Key::Alt('f') => editor.cursor = editor.word_end(),
+            Key::Ctrl('k') => editor.kill(editor.cursor, editor.buffer.len()),
+            Key::Ctrl('u') => editor.kill(0, editor.cursor),
+            Key::Ctrl('w') => editor.kill(editor.word_start_whitespace(), editor.cursor),
+            Key::Alt('\x7f') => editor.kill(editor.word_start(), editor.cursor),
+            Key::Alt('d') => editor.kill(editor.cursor, editor.word_end()),
+            Key::Ctrl('y') => {
+                for c in editor.killed.clone() {
+                    editor.insert(c);
+                }
+            },
+            // Swap the characters around the cursor
+            Key::Ctrl('t') if editor.cursor > 0 && editor.buffer.len() > 1 => {
+                if editor.cursor == editor.buffer.len() {
+                    editor.cursor -= 1;
+                }
+                editor.buffer.swap(editor.cursor - 1, editor.cursor);
+                editor.cursor += 1;
+            },
+            Key::Up | Key::Ctrl('p') => editor.history_previous(),
+            Key::Down | Key::Ctrl('n') => editor.history_next(),
+            Key::Ctrl('r') => pending = editor.reverse_search()?,
+            Key::Ctrl('l') => {
+                print!("\x1b[2J\x1b[H");
+                editor.cursor_row = 0;
+            },
+            _ => (),
+        }
+        last_key = Some(key);
+        editor.render();
+    }
+}
@@ -0,0 +1,857 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::FromRawFd;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use crate::ast::{self, AndOr, CompoundCommand, Connector, List, Pipeline, Redirection, SimpleCommand};
+use crate::redirect::{self, Redirect, SavedFds};
+use crate::{build_in, exit_shell, expand, hash, jobs, options, path_search, pattern, save_status, time, trap, vars, BUILD_INS};
+
+/// A simple command after expansion
+pub struct Command {
+    /// Prefix assignments like `VAR=value cmd`, only visible to this command
+    pub assignments: Vec<(String, String)>,
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// The result of expanding a simple command
+enum Expanded {
+    Command(Command),
+    /// Nothing but assignments and redirections
+    Empty(Vec<(String, String)>, Vec<Redirect>),
+}
+
+fn expand_redirects(redirects: &[Redirection]) -> Result<Vec<Redirect>, String> {
+    redirects.iter()
+        .map(|r| Ok(Redirect { fd: r.fd, kind: r.kind, target: expand::expand_word(&r.target)? }))
+        .collect()
+}
+
+fn expand_command(command: &SimpleCommand) -> Result<Expanded, String> {
+    let mut assignments = vec![];
+    for assignment in &command.assignments {
+        assignments.push((assignment.name.clone(), expand::expand_word(&assignment.value)?));
+    }
+    let redirects = expand_redirects(&command.redirects)?;
+    let mut args = expand::expand_words(&command.words)?;
+    if args.is_empty() {
+        return Ok(Expanded::Empty(assignments, redirects));
+    }
+    let cmd = args.remove(0);
+    Ok(Expanded::Command(Command { assignments, cmd, args, redirects }))
+}
+
+/// Set by break, continue and return to unwind the commands being run
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    Break(usize),
+    Continue(usize),
+    Return,
+    /// `exit` in an embedded shell, which can't end the process
+    Exit(i32),
+}
+
+static FLOW: Mutex<Option<Flow>> = Mutex::new(None);
+
+/// Set for a shell run by another program, see [exit] and [embed]
+static EMBEDDED: AtomicBool = AtomicBool::new(false);
+
+/// Number of loops being run by the current function, or outside of functions
+static LOOP_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+static FUNCTION_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of files being run by the source build_in, `return` ends the innermost one
+static SOURCE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of conditions being run, like that of `if` or the left side of `&&`, where `set -e` is ignored
+static CONDITION_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Status of the last command substitution, which becomes the status of a command without a name
+static SUBSTITUTION_STATUS: AtomicI32 = AtomicI32::new(0);
+
+/// Function bodies by name
+pub type Functions = HashMap<String, Arc<ast::Command>>;
+
+/// Functions defined with `name() body`
+static FUNCTIONS: LazyLock<Mutex<Functions>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The functions and the commands being run of a shell while it isn't its turn, see [swap]
+#[derive(Default)]
+pub struct State {
+    functions: Functions,
+    flow: Option<Flow>,
+    embedded: bool,
+    loop_depth: usize,
+    function_depth: usize,
+    source_depth: usize,
+    condition_depth: usize,
+    substitution_status: i32,
+}
+
+/// Exchange the functions and the commands being run with those of another shell
+pub fn swap(state: &mut State) {
+    std::mem::swap(&mut *FUNCTIONS.lock().unwrap(), &mut state.functions);
+    std::mem::swap(&mut *FLOW.lock().unwrap(), &mut state.flow);
+    state.embedded = EMBEDDED.swap(state.embedded, Ordering::Relaxed);
+    state.loop_depth = LOOP_DEPTH.swap(state.loop_depth, Ordering::Relaxed);
+    state.function_depth = FUNCTION_DEPTH.swap(state.function_depth, Ordering::Relaxed);
+    state.source_depth = SOURCE_DEPTH.swap(state.source_depth, Ordering::Relaxed);
+    state.condition_depth = CONDITION_DEPTH.swap(state.condition_depth, Ordering::Relaxed);
+    state.substitution_status = SUBSTITUTION_STATUS.swap(state.substitution_status, Ordering::Relaxed);
+}
+
+pub fn function(name: &str) -> Option<Arc<ast::Command>> {
+    FUNCTIONS.lock().unwrap().get(name).cloned()
+}
+
+/// Whether the commands being run should stop because of break, continue, return or ctrl-C
+pub fn unwinding() -> bool {
+    FLOW.lock().unwrap().is_some() || trap::pending(libc::SIGINT)
+}
+
+/// Handle a break or continue meant for the loop being run, returns whether the loop should stop
+fn end_loop() -> bool {
+    if trap::pending(libc::SIGINT) {
+        return true;
+    }
+    let mut flow = FLOW.lock().unwrap();
+    match *flow {
+        None => false,
+        Some(Flow::Break(1)) => {
+            *flow = None;
+            true
+        },
+        Some(Flow::Break(n)) => {
+            *flow = Some(Flow::Break(n - 1));
+            true
+        },
+        Some(Flow::Continue(1)) => {
+            *flow = None;
+            false
+        },
+        // Continuing an outer loop stops this one
+        Some(Flow::Continue(n)) => {
+            *flow = Some(Flow::Continue(n - 1));
+            true
+        },
+        Some(Flow::Return | Flow::Exit(_)) => true,
+    }
+}
+
+/// Make `exit` end the commands being evaluated instead of the process, for the shell whose turn it is
+pub fn embed() {
+    EMBEDDED.store(true, Ordering::Relaxed);
+}
+
+/// Exit the shell, or unwind to the caller of an embedded shell. Returns the code for the latter.
+pub fn exit(code: i32) -> i32 {
+    if !EMBEDDED.load(Ordering::Relaxed) {
+        exit_shell(code);
+    }
+    *FLOW.lock().unwrap() = Some(Flow::Exit(code));
+    code
+}
+
+/// The code of an `exit` that unwound an embedded shell, clearing it
+pub fn take_exit() -> Option<i32> {
+    let mut flow = FLOW.lock().unwrap();
+    match *flow {
+        Some(Flow::Exit(code)) => {
+            *flow = None;
+            Some(code)
+        },
+        _ => None,
+    }
+}
+
+/// Run a parsed list, returns the status of the last command
+pub fn run(list: &List) -> Option<i32> {
+    let mut status = Some(0);
+    for item in &list.items {
+        status = if item.background {
+            run_background(item)
+        } else {
+            run_and_or(item)
+        };
+        save_status(status);
+        if unwinding() {
+            break;
+        }
+    }
+    status
+}
+
+/// Run a list whose status is tested, `set -e` doesn't apply to it
+fn run_condition(f: impl FnOnce() -> Option<i32>) -> Option<i32> {
+    CONDITION_DEPTH.fetch_add(1, Ordering::Relaxed);
+    let status = f();
+    CONDITION_DEPTH.fetch_sub(1, Ordering::Relaxed);
+    status
+}
+
+/// Run the pipelines of `a && b || c` depending on the status of the previous one
+fn run_and_or(and_or: &AndOr) -> Option<i32> {
+    let pipelines: Vec<&Pipeline> = std::iter::once(&and_or.first)
+        .chain(and_or.rest.iter().map(|(_, pipeline)| pipeline))
+        .collect();
+    let last = pipelines.len() - 1;
+    // Only the last pipeline can make the shell exit with `set -e`
+    let run_tested = |i: usize| match i == last {
+        true => run_pipeline(pipelines[i], false),
+        false => run_condition(|| run_pipeline(pipelines[i], false)),
+    };
+    let mut status = run_tested(0);
+    let mut ran_last = last == 0;
+    for (i, (connector, _)) in and_or.rest.iter().enumerate() {
+        if unwinding() {
+            break;
+        }
+        save_status(status);
+        let run = match connector {
+            Connector::And => status == Some(0),
+            Connector::Or => status != Some(0),
+        };
+        if run {
+            status = run_tested(i + 1);
+            ran_last = i + 1 == last;
+        }
+    }
+    if ran_last && status != Some(0) {
+        errexit(pipelines[last], status);
+    }
+    status
+}
+
+/// Exit the shell for `set -e` when a pipeline failed outside of a condition.
+/// Groups, loops and the like aren't checked, the commands in them already were.
+fn errexit(pipeline: &Pipeline, status: Option<i32>) {
+    let compound = matches!(pipeline.commands.as_slice(),
+        [ast::Command::Compound(compound, _)] if !matches!(compound, CompoundCommand::Subshell(_)));
+    if !options::enabled("errexit") || pipeline.negated || compound || unwinding()
+        || CONDITION_DEPTH.load(Ordering::Relaxed) > 0 {
+        return;
+    }
+    exit(status.unwrap_or(127));
+}
+
+fn run_background(and_or: &AndOr) -> Option<i32> {
+    // A timed pipeline needs a subshell to time it
+    if and_or.rest.is_empty() && and_or.first.time.is_none() {
+        return run_pipeline(&and_or.first, true);
+    }
+    // The whole and-or list becomes a single job run by a subshell
+    spawn(vec![Stage::AndOr(and_or)], true, and_or.to_string())
+}
+
+fn run_pipeline(pipeline: &Pipeline, background: bool) -> Option<i32> {
+    let run = || match pipeline.commands.as_slice() {
+        [] => Some(0),
+        [command] if !background => run_foreground(command),
+        commands => spawn(commands.iter().map(Stage::Command).collect(), background, pipeline.to_string()),
+    };
+    let run = || match pipeline.negated {
+        true => {
+            let status = run_condition(run);
+            Some(if status == Some(0) { 1 } else { 0 })
+        },
+        false => run(),
+    };
+    match pipeline.time {
+        Some(format) => time::time(format, run),
+        None => run(),
+    }
+}
+
+/// Run a lone command in the foreground, build_ins, assignments,
+/// groups and functions run in the shell itself so they can affect it
+fn run_foreground(command: &ast::Command) -> Option<i32> {
+    match command {
+        ast::Command::Simple(simple) => run_simple(simple, command),
+        ast::Command::Compound(CompoundCommand::Subshell(_), _) => {
+            spawn(vec![Stage::Command(command)], false, command.to_string())
+        },
+        ast::Command::Compound(compound, redirects) => {
+            let redirects = match expand_redirects(redirects) {
+                Ok(redirects) => redirects,
+                Err(e) => {
+                    eprintln!("schelp: {e}");
+                    return Some(1);
+                },
+            };
+            // Redirect the shell's own fds for the duration of the command
+            let saved = match SavedFds::apply(&redirects) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    eprintln!("schelp: {e}");
+                    return Some(1)
+                },
+            };
+            let status = run_compound(compound);
+            saved.restore();
+            status
+        },
+        ast::Command::Function(name, body) => {
+            FUNCTIONS.lock().unwrap().insert(name.clone(), Arc::new((**body).clone()));
+            Some(0)
+        },
+    }
+}
+
+fn run_simple(simple: &SimpleCommand, text: &ast::Command) -> Option<i32> {
+    SUBSTITUTION_STATUS.store(0, Ordering::Relaxed);
+    let command = match expand_command(simple) {
+        Ok(Expanded::Command(command)) => command,
+        Ok(Expanded::Empty(assignments, redirects)) => {
+            // Without a command the redirections are still performed
+            // and the assignments are made in the shell itself
+            match SavedFds::apply(&redirects) {
+                Ok(saved) => saved.restore(),
+                Err(e) => {
+                    eprintln!("schelp: {e}");
+                    return Some(1);
+                },
+            }
+            trace(&assignments, []);
+            for (name, value) in &assignments {
+                vars::set(name, value);
+            }
+            return Some(SUBSTITUTION_STATUS.load(Ordering::Relaxed));
+        },
+        Err(e) => return expansion_failed(e),
+    };
+    trace(&command.assignments, std::iter::once(&command.cmd).chain(&command.args));
+    if let Some(body) = function(&command.cmd) {
+        return call_function(&body, &command);
+    }
+    if BUILD_INS.contains(&command.cmd.as_str()) {
+        return run_build_in(&command);
+    }
+    let Some(path) = find_command(&command.cmd) else {
+        return Some(not_found(&command.cmd));
+    };
+    spawn(vec![Stage::Exec(path, command)], false, text.to_string())
+}
+
+/// Report an expansion error like an unset variable with `set -u`, a script exits on one
+fn expansion_failed(e: String) -> Option<i32> {
+    eprintln!("schelp: {e}");
+    if !trap::interactive() {
+        return Some(exit(1));
+    }
+    Some(1)
+}
+
+/// Print an expanded command to stderr after PS4 for `set -x`
+fn trace<'a>(assignments: &[(String, String)], words: impl IntoIterator<Item = &'a String>) {
+    if !options::enabled("xtrace") {
+        return;
+    }
+    let fields: Vec<String> = assignments.iter()
+        .map(|(name, value)| format!("{name}={}", quote(value)))
+        .chain(words.into_iter().map(|word| quote(word)))
+        .collect();
+    eprintln!("{}{}", vars::get("PS4").unwrap_or("+ ".to_owned()), fields.join(" "));
+}
+
+/// Quote a word that would be split or expanded when read back
+fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:,+%@=".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_owned();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Run the body of a compound command in the current process
+fn run_compound(compound: &CompoundCommand) -> Option<i32> {
+    match compound {
+        CompoundCommand::BraceGroup(list) | CompoundCommand::Subshell(list) => run(list),
+        CompoundCommand::If { branches, otherwise } => {
+            for (condition, body) in branches {
+                let status = run_condition(|| run(condition));
+                if unwinding() {
+                    return status;
+                }
+                if status == Some(0) {
+                    return run(body);
+                }
+            }
+            match otherwise {
+                Some(otherwise) => run(otherwise),
+                None => Some(0),
+            }
+        },
+        CompoundCommand::While { condition, body, until } => {
+            LOOP_DEPTH.fetch_add(1, Ordering::Relaxed);
+            let mut status = Some(0);
+            loop {
+                let result = run_condition(|| run(condition));
+                if end_loop() || (result == Some(0)) == *until {
+                    break;
+                }
+                status = run(body);
+                if end_loop() {
+                    break;
+                }
+            }
+            LOOP_DEPTH.fetch_sub(1, Ordering::Relaxed);
+            status
+        },
+        CompoundCommand::For { var, words, body } => {
+            let items = match words {
+                Some(words) => match expand::expand_words(words) {
+                    Ok(items) => items,
+                    Err(e) => return expansion_failed(e),
+                },
+                None => vars::positional(),
+            };
+            LOOP_DEPTH.fetch_add(1, Ordering::Relaxed);
+            let mut status = Some(0);
+            for item in items {
+                vars::set(var, &item);
+                status = run(body);
+                if end_loop() {
+                    break;
+                }
+            }
+            LOOP_DEPTH.fetch_sub(1, Ordering::Relaxed);
+            status
+        },
+        CompoundCommand::Case { word, arms } => {
+            let word = match expand::expand_word(word) {
+                Ok(word) => word,
+                Err(e) => return expansion_failed(e),
+            };
+            for arm in arms {
+                for pattern in &arm.patterns {
+                    match expand::expand_pattern(pattern) {
+                        Ok(pattern) if pattern::matches(&pattern, &word) => return run(&arm.body),
+                        Ok(_) => (),
+                        Err(e) => {
+                            eprintln!("schelp: {e}");
+                            return Some(1);
+                        },
+                    }
+                }
+            }
+            Some(0)
+        },
+    }
+}
+
+/// Run a function with the arguments as its positional parameters
+fn call_function(body: &ast::Command, command: &Command) -> Option<i32> {
+    with_command_env(command, || {
+        let params = vars::set_positional(command.args.clone());
+        vars::push_scope();
+        // Loops of the caller can't be broken out of
+        let loops = LOOP_DEPTH.swap(0, Ordering::Relaxed);
+        FUNCTION_DEPTH.fetch_add(1, Ordering::Relaxed);
+
+        let status = run_foreground(body);
+
+        FUNCTION_DEPTH.fetch_sub(1, Ordering::Relaxed);
+        LOOP_DEPTH.store(loops, Ordering::Relaxed);
+        vars::pop_scope();
+        vars::set_positional(params);
+        let mut flow = FLOW.lock().unwrap();
+        if *flow == Some(Flow::Return) {
+            *flow = None;
+        }
+        status
+    })
+}
+
+/// The break and continue build_ins
+pub fn loop_control(cmd: &str, args: &[String]) -> i32 {
+    let n = match args.first().map(|n| n.parse::<usize>()) {
+        None => 1,
+        Some(Ok(n)) if n > 0 => n,
+        _ => {
+            eprintln!("{cmd}: {}: loop count out of range", args[0]);
+            return 1;
+        },
+    };
+    let depth = LOOP_DEPTH.load(Ordering::Relaxed);
+    if depth == 0 {
+        eprintln!("{cmd}: only meaningful in a loop");
+        return 0;
+    }
+    let n = n.min(depth);
+    *FLOW.lock().unwrap() = Some(if cmd == "break" { Flow::Break(n) } else { Flow::Continue(n) });
+    0
+}
+
+/// The return build_in, the status defaults to that of the last command
+pub fn return_from_function(args: &[String]) -> i32 {
+    if FUNCTION_DEPTH.load(Ordering::Relaxed) == 0 && SOURCE_DEPTH.load(Ordering::Relaxed) == 0 {
+        eprintln!("return: can only be used in a function or sourced file");
+        return 1;
+    }
+    let status = match args.first() {
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(status) => status & 0xff,
+            Err(_) => {
+                eprintln!("return: {arg}: numeric argument required");
+                2
+            },
+        },
+        None => vars::get("status").and_then(|s| s.parse().ok()).unwrap_or(0),
+    };
+    *FLOW.lock().unwrap() = Some(Flow::Return);
+    status
+}
+
+/// Run a sourced file, a `return` in it only ends the file
+pub fn source(f: impl FnOnce()) {
+    SOURCE_DEPTH.fetch_add(1, Ordering::Relaxed);
+    f();
+    SOURCE_DEPTH.fetch_sub(1, Ordering::Relaxed);
+    let mut flow = FLOW.lock().unwrap();
+    if *flow == Some(Flow::Return) {
+        *flow = None;
+    }
+}
+
+/// Run an already expanded command in the foreground, used by `env` and `command`
+pub fn run_command(command: Command) -> Option<i32> {
+    if BUILD_INS.contains(&command.cmd.as_str()) {
+        return run_build_in(&command);
+    }
+    let Some(path) = find_command(&command.cmd) else {
+        return Some(not_found(&command.cmd));
+    };
+    let text = command.cmd.clone();
+    spawn(vec![Stage::Exec(path, command)], false, text)
+}
+
+/// What a forked process of a pipeline runs
+enum Stage<'a> {
+    /// An expanded command whose executable was already found
+    Exec(String, Command),
+    /// A command that is expanded in the child
+    Command(&'a ast::Command),
+    /// A whole and-or list run by a subshell
+    AndOr(&'a AndOr),
+}
+
+/// Fork the processes of a pipeline and run them as a job
+fn spawn(stages: Vec<Stage>, background: bool, text: String) -> Option<i32> {
+    // Libc is used here instead of Rusts Command and Child struct
+    // for better control. It also fits the projects philosophy better.
+    let foreground = !background;
+    let count = stages.len();
+    let mut pgid = 0;
+    let mut pids = vec![];
+    // Read end of the previous pipe
+    let mut input = None;
+    // Set when a stage couldn't be started, for example at the process limit
+    let mut error = None;
+    for (i, stage) in stages.into_iter().enumerate() {
+        let (next_input, output) = if i + 1 < count {
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+                error = Some(format!("Failed to create pipe: {}", io::Error::last_os_error()));
+                break;
+            }
+            (Some(fds[0]), Some(fds[1]))
+        } else {
+            (None, None)
+        };
+
+        let pid = unsafe { libc::fork() };
+        if pid == -1 {
+            error = Some(format!("Failed to fork: {}", io::Error::last_os_error()));
+            for fd in [output, next_input].into_iter().flatten() {
+                unsafe { libc::close(fd) };
+            }
+            break;
+        }
+
+        if pid == 0 {
+            jobs::child_setup(pgid, foreground);
+            // Whatever the stage runs, its own children belong to this job
+            jobs::subshell();
+            trap::reset_child();
+            unsafe {
+                if let Some(fd) = input {
+                    libc::dup2(fd, libc::STDIN_FILENO);
+                }
+                if let Some(fd) = output {
+                    libc::dup2(fd, libc::STDOUT_FILENO);
+                }
+                // Stages that don't exec would otherwise keep the pipes open,
+                // a writer holding its own read end never sees EPIPE
+                for fd in [input, output, next_input].into_iter().flatten() {
+                    libc::close(fd);
+                }
+            }
+            match stage {
+                Stage::Exec(path, command) => fork_child(path, &command),
+                Stage::Command(ast::Command::Simple(simple)) => run_in_child(simple),
+                Stage::Command(ast::Command::Function(..)) => exit_child(0),
+                Stage::Command(ast::Command::Compound(compound, redirects)) => {
+                    let code = match expand_redirects(redirects).and_then(|r| redirect::apply(&r).map_err(|e| e.to_string())) {
+                        Ok(()) => run_compound(compound).unwrap_or(127),
+                        Err(e) => {
+                            eprintln!("schelp: {e}");
+                            1
+                        },
+                    };
+                    exit_child(code)
+                },
+                Stage::AndOr(and_or) => exit_child(run_and_or(and_or).unwrap_or(127)),
+            }
+        }
+
+        // Also set the process group from the parent to avoid racing the child
+        if pgid == 0 {
+            pgid = pid;
+        }
+        if jobs::job_control() {
+            unsafe { libc::setpgid(pid, pgid) };
+        }
+        unsafe {
+            if let Some(fd) = input {
+                libc::close(fd);
+            }
+            if let Some(fd) = output {
+                libc::close(fd);
+            }
+        }
+        input = next_input;
+        pids.push(pid);
+    }
+
+    if let Some(error) = error {
+        eprintln!("schelp: {error}");
+        if let Some(fd) = input {
+            unsafe { libc::close(fd) };
+        }
+        // The stages already running see the end of their pipes, wait so they don't linger
+        if !pids.is_empty() {
+            let id = jobs::add(pgid, pids, text);
+            if foreground {
+                jobs::foreground(id, false);
+            }
+        }
+        return Some(1);
+    }
+
+    let last = *pids.last().unwrap();
+    let id = jobs::add(pgid, pids, text);
+    if foreground {
+        jobs::foreground(id, false)
+    } else {
+        if trap::interactive() {
+            println!("[{id}] {last}");
+        }
+        vars::set_last_background(last);
+        Some(0)
+    }
+}
+
+/// Expand and run a simple command in a forked process
+fn run_in_child(simple: &SimpleCommand) -> ! {
+    let code = match expand_command(simple) {
+        Ok(Expanded::Command(command)) => {
+            trace(&command.assignments, std::iter::once(&command.cmd).chain(&command.args));
+            if let Some(body) = function(&command.cmd) {
+                exit_child(call_function(&body, &command).unwrap_or(127));
+            }
+            if !BUILD_INS.contains(&command.cmd.as_str()) {
+                match find_command(&command.cmd) {
+                    Some(path) => fork_child(path, &command),
+                    None => exit_child(not_found(&command.cmd)),
+                }
+            }
+            run_build_in(&command).unwrap_or(1)
+        },
+        Ok(Expanded::Empty(_, redirects)) => match redirect::apply(&redirects) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("schelp: {e}");
+                1
+            },
+        },
+        Err(e) => {
+            eprintln!("schelp: {e}");
+            1
+        },
+    };
+    exit_child(code)
+}
+
+/// Run a list with its output captured for `$(list)`, trailing newlines are removed
+pub fn substitute(list: &List) -> Result<String, String> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(format!("Failed to create pipe: {}", io::Error::last_os_error()));
+    }
+    // Don't let the child inherit buffered output
+    io::stdout().flush().ok();
+    let pid = unsafe { libc::fork() };
+    if pid == -1 {
+        let error = io::Error::last_os_error();
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+        return Err(format!("Failed to fork: {error}"));
+    }
+    if pid == 0 {
+        jobs::subshell();
+        trap::reset_child();
+        unsafe { libc::dup2(fds[1], libc::STDOUT_FILENO) };
+        exit_child(run(list).unwrap_or(127));
+    }
+
+    unsafe { libc::close(fds[1]) };
+    let mut output = vec![];
+    let result = unsafe { File::from_raw_fd(fds[0]) }.read_to_end(&mut output);
+
+    let mut wstatus = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    while unsafe { libc::wait4(pid, &mut wstatus, 0, &mut usage) } == -1 {
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            break;
+        }
+    }
+    time::record(&usage);
+    let status = if libc::WIFSIGNALED(wstatus) {
+        128 + libc::WTERMSIG(wstatus)
+    } else {
+        libc::WEXITSTATUS(wstatus)
+    };
+    SUBSTITUTION_STATUS.store(status, Ordering::Relaxed);
+    result.map_err(|e| format!("Failed to read command output: {e}"))?;
+
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    while output.ends_with('\n') {
+        output.pop();
+    }
+    Ok(output)
+}
+
+fn exit_child(code: i32) -> ! {
+    io::stdout().flush().ok();
+    std::process::exit(code);
+}
+
+fn run_build_in(command: &Command) -> Option<i32> {
+    if command.cmd == "exec" {
+        return Some(exec(command));
+    }
+    with_command_env(command, || build_in(&command.cmd, &command.args))
+}
+
+/// The exec build_in. The command replaces the shell,
+/// without one the redirections stay in effect for the shell.
+fn exec(command: &Command) -> i32 {
+    let Some((cmd, args)) = command.args.split_first() else {
+        if let Err(e) = redirect::apply(&command.redirects) {
+            eprintln!("exec: {e}");
+            return 1;
+        }
+        for (name, value) in &command.assignments {
+            vars::set(name, value);
+        }
+        return 0;
+    };
+    let Some(path) = find_command(cmd) else {
+        eprintln!("exec: {cmd}: not found");
+        return 127;
+    };
+    let command = Command {
+        assignments: command.assignments.clone(),
+        cmd: cmd.clone(),
+        args: args.to_vec(),
+        redirects: command.redirects.clone(),
+    };
+    io::stdout().flush().ok();
+    trap::reset_child();
+    fork_child(path, &command)
+}
+
+/// Run a build_in or function with the redirections and prefix assignments of the command
+fn with_command_env(command: &Command, f: impl FnOnce() -> Option<i32>) -> Option<i32> {
+    // Redirect the shell's own fds for the duration of the command
+    let saved = match SavedFds::apply(&command.redirects) {
+        Ok(saved) => saved,
+        Err(e) => {
+            eprintln!("schelp: {e}");
+            return Some(1)
+        },
+    };
+    // Prefix assignments are exported for the duration of the command
+    let assigned: Vec<vars::Saved> = command.assignments.iter()
+        .map(|(name, value)| vars::assign_exported(name, value))
+        .collect();
+    let code = f();
+    for var in assigned.into_iter().rev() {
+        vars::restore(var);
+    }
+    saved.restore();
+    code
+}
+
+/// A command with a slash is a path, others are looked up in PATH
+pub fn find_command(cmd: &str) -> Option<String> {
+    if cmd.contains('/') {
+        return Path::new(cmd).exists().then(|| cmd.to_owned());
+    }
+    hash::lookup(cmd).map(|path| path.to_string_lossy().to_string())
+}
+
+/// Report a command that can't be run, returns 126 when it was found but isn't executable
+fn not_found(cmd: &str) -> i32 {
+    if !cmd.contains('/') && path_search(&vars::get("PATH").unwrap_or_default(), cmd, false).is_some() {
+        eprintln!("schelp: {cmd}: Permission denied");
+        return 126;
+    }
+    eprintln!("schelp: {cmd}: command not found");
+    127
+}
+
+// TODO have our own nix crate which handles execve and stuff
+// with rusty return types
+fn fork_child(path: String, command: &Command) -> ! {
+    if let Err(e) = redirect::apply(&command.redirects) {
+        eprintln!("schelp: {e}");
+        std::process::exit(1);
+    }
+
+    // This isn't exec(3) so we'll have to do PATHs ourselves
+    let env = vars::environ(&command.assignments);
+    let mut env_ptrs: Vec<*const i8> = env.iter().map(|f| f.as_ptr()).collect();
+    env_ptrs.push(ptr::null());
+
+    // Create a clone of args with cmd included as argv
+    let mut argv = vec![path.clone()];
+    argv.append(&mut command.args.clone());
+    let argv: Vec<CString> = argv.iter().map(|a| CString::new(a.as_str()).unwrap()).collect();
+
+    let cmd_cstr = CString::new(path.as_str()).unwrap();
+    let mut arg_ptrs: Vec<*const i8> = argv.iter().map(|f| f.as_ptr()).collect();
+    arg_ptrs.push(ptr::null());
+
+    let r: i32 = unsafe { libc::execve(
+        cmd_cstr.as_ptr(),
+        arg_ptrs.as_ptr(),
+        env_ptrs.as_ptr()
+    ) };
+    if r == -1 {
+        let e = io::Error::last_os_error();
+        eprintln!("schelp: {path}: {e}");
+        // A file that is missing can't be found, any other file can't be run
+        std::process::exit(if e.kind() == io::ErrorKind::NotFound { 127 } else { 126 });
+    }
+    unreachable!();
+}
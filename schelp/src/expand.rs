@@ -0,0 +1,450 @@
+use crate::ast::{Param, ParamOp, Word, WordPart};
+use crate::{arith, exec, glob, lexer, options, pattern, vars};
+
+/// Characters fields are split on when IFS is unset
+const DEFAULT_IFS: &str = " \t\n";
+
+/// The value of an unset parameter, an error with `set -u`
+fn unset(name: &str) -> Result<String, String> {
+    if options::enabled("nounset") && name != "@" && name != "*" {
+        return Err(format!("{name}: unbound variable"));
+    }
+    Ok(String::new())
+}
+
+fn param(param: &Param) -> Result<String, String> {
+    let value = vars::get(&param.name);
+    // Whether the value counts as set for the operators, with a colon empty values don't
+    let is_set = |colon: bool| value.as_ref().is_some_and(|v| !colon || !v.is_empty());
+    let Some(op) = &param.op else {
+        return value.map_or_else(|| unset(&param.name), Ok);
+    };
+    match op {
+        ParamOp::Length => {
+            let value = value.map_or_else(|| unset(&param.name), Ok)?;
+            Ok(value.chars().count().to_string())
+        },
+        ParamOp::Default { word, colon } => match is_set(*colon) {
+            true => Ok(value.unwrap()),
+            false => expand_word(word),
+        },
+        ParamOp::Assign { word, colon } => {
+            if is_set(*colon) {
+                return Ok(value.unwrap());
+            }
+            if !lexer::is_name(&param.name) {
+                return Err(format!("${}: cannot assign in this way", param.name));
+            }
+            let value = expand_word(word)?;
+            vars::set(&param.name, &value);
+            Ok(value)
+        },
+        ParamOp::Error { word, colon } => {
+            if is_set(*colon) {
+                return Ok(value.unwrap());
+            }
+            let message = expand_word(word)?;
+            match message.is_empty() {
+                true => Err(format!("{}: parameter null or not set", param.name)),
+                false => Err(format!("{}: {message}", param.name)),
+            }
+        },
+        ParamOp::Alternative { word, colon } => match is_set(*colon) {
+            true => expand_word(word),
+            false => Ok(String::new()),
+        },
+        ParamOp::RemovePrefix { pattern, longest } => {
+            let value = value.map_or_else(|| unset(&param.name), Ok)?;
+            let pattern = expand_pattern(pattern)?;
+            let chars: Vec<char> = value.chars().collect();
+            let mut ends: Vec<usize> = (0..=chars.len()).collect();
+            if *longest {
+                ends.reverse();
+            }
+            Ok(match ends.into_iter().find(|end| pattern::matches(&pattern, &chars[..*end].iter().collect::<String>())) {
+                Some(end) => chars[end..].iter().collect(),
+                None => value,
+            })
+        },
+        ParamOp::RemoveSuffix { pattern, longest } => {
+            let value = value.map_or_else(|| unset(&param.name), Ok)?;
+            let pattern = expand_pattern(pattern)?;
+            let chars: Vec<char> = value.chars().collect();
+            let mut starts: Vec<usize> = (0..=chars.len()).collect();
+            if !*longest {
+                starts.reverse();
+            }
+            Ok(match starts.into_iter().find(|start| pattern::matches(&pattern, &chars[*start..].iter().collect::<String>())) {
+                Some(start) => chars[..start].iter().collect(),
+                None => value,
+            })
+        },
+    }
+}
+
+/// Expand the parts of a word without field splitting
+fn expand_parts(parts: &[WordPart], out: &mut String) -> Result<(), String> {
+    for part in parts {
+        match part {
+            WordPart::Literal(text) | WordPart::Quoted(text) => out.push_str(text),
+            WordPart::DoubleQuoted(parts) => expand_parts(parts, out)?,
+            WordPart::Param(p) => out.push_str(&param(p)?),
+            WordPart::CommandSub(list) => out.push_str(&exec::substitute(list)?),
+            WordPart::Arith(parts) => out.push_str(&arith(parts)?),
+        }
+    }
+    Ok(())
+}
+
+/// Evaluate `$((expression))` after expanding the parameters in it
+fn arith(parts: &[WordPart]) -> Result<String, String> {
+    let mut expr = String::new();
+    expand_parts(parts, &mut expr)?;
+    arith::eval(&expr).map(|n| n.to_string())
+}
+
+/// Expand a word to a single string, used for assignments and redirection targets
+pub fn expand_word(word: &Word) -> Result<String, String> {
+    let mut out = String::new();
+    expand_parts(&tilde_expand(word).parts, &mut out)?;
+    Ok(out)
+}
+
+/// Escape the characters that are special in patterns
+fn escape_pattern(text: &str, out: &mut String) {
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+/// Expand a word used as a pattern, quoted characters match literally
+pub fn expand_pattern(word: &Word) -> Result<String, String> {
+    let mut out = String::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(text) => out.push_str(text),
+            WordPart::Quoted(text) => escape_pattern(text, &mut out),
+            WordPart::DoubleQuoted(parts) => {
+                let mut text = String::new();
+                expand_parts(parts, &mut text)?;
+                escape_pattern(&text, &mut out);
+            },
+            WordPart::Param(p) => out.push_str(&param(p)?),
+            WordPart::CommandSub(list) => out.push_str(&exec::substitute(list)?),
+            WordPart::Arith(parts) => out.push_str(&arith(parts)?),
+        }
+    }
+    Ok(out)
+}
+
+/// Split the result of an unquoted expansion on IFS.
+/// Returns the fields and whether the value started and ended with a delimiter.
+fn split_fields(value: &str, ifs: &str) -> (bool, Vec<String>, bool) {
+    let is_whitespace = |c: char| c.is_whitespace() && ifs.contains(c);
+    let is_delimiter = |c: char| ifs.contains(c);
+    let chars: Vec<char> = value.chars().collect();
+    let starts = chars.first().is_some_and(|c| is_delimiter(*c));
+    let ends = chars.last().is_some_and(|c| is_delimiter(*c));
+
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut i = 0;
+    while i < chars.len() && is_whitespace(chars[i]) {
+        i += 1;
+    }
+    while i < chars.len() {
+        if !is_delimiter(chars[i]) {
+            field.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        // A delimiter is IFS whitespace around at most one other IFS character
+        let mut other = false;
+        while i < chars.len() && is_delimiter(chars[i]) {
+            if !is_whitespace(chars[i]) {
+                if other {
+                    break;
+                }
+                other = true;
+            }
+            i += 1;
+        }
+        fields.push(std::mem::take(&mut field));
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    (starts, fields, ends)
+}
+
+/// Builds the fields a list of words expands to
+#[derive(Default)]
+struct Fields {
+    fields: Vec<String>,
+    current: String,
+    /// The current field as a pattern, quoted characters are escaped
+    pattern: String,
+    /// Whether the current field has unquoted glob characters
+    glob: bool,
+    /// Whether there is a current field, it can be empty when it was quoted
+    started: bool,
+}
+
+impl Fields {
+    /// Add quoted text, which is not split or globbed
+    fn push_str(&mut self, text: &str) {
+        self.current.push_str(text);
+        escape_pattern(text, &mut self.pattern);
+        self.started = true;
+    }
+
+    /// Add unquoted text, which can contain glob characters
+    fn push_unquoted(&mut self, text: &str) {
+        self.current.push_str(text);
+        self.pattern.push_str(text);
+        self.glob |= glob::has_glob(text);
+        self.started = true;
+    }
+
+    fn finish(&mut self) {
+        if !self.started {
+            return;
+        }
+        let field = std::mem::take(&mut self.current);
+        let pattern = std::mem::take(&mut self.pattern);
+        // Patterns that match nothing are left as they are
+        let matches = if std::mem::take(&mut self.glob) { glob::glob(&pattern) } else { vec![] };
+        if matches.is_empty() {
+            self.fields.push(field);
+        } else {
+            self.fields.extend(matches);
+        }
+        self.started = false;
+    }
+
+    /// Add the result of an unquoted expansion, which is split into fields
+    fn push_split(&mut self, value: &str, ifs: &str) {
+        let (starts, pieces, ends) = split_fields(value, ifs);
+        if starts {
+            self.finish();
+        }
+        for (i, piece) in pieces.iter().enumerate() {
+            if i > 0 {
+                self.finish();
+            }
+            self.push_unquoted(piece);
+        }
+        if ends {
+            self.finish();
+        }
+    }
+}
+
+/// A word split into unquoted characters, which brace expansion works on, and other parts
+#[derive(Clone)]
+enum Item {
+    Char(char),
+    Part(WordPart),
+}
+
+fn to_items(word: &Word) -> Vec<Item> {
+    let mut items = vec![];
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(text) => items.extend(text.chars().map(Item::Char)),
+            part => items.push(Item::Part(part.clone())),
+        }
+    }
+    items
+}
+
+fn to_word(items: &[Item]) -> Word {
+    let mut parts = vec![];
+    let mut literal = String::new();
+    for item in items {
+        match item {
+            Item::Char(c) => literal.push(*c),
+            Item::Part(part) => {
+                if !literal.is_empty() {
+                    parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(part.clone());
+            },
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal(literal));
+    }
+    Word { parts }
+}
+
+/// The words `{1..5}` or `{a..e}` expands to, with an optional increment like `{1..10..2}`
+fn sequence(items: &[Item]) -> Option<Vec<String>> {
+    let text = items.iter()
+        .map(|item| match item {
+            Item::Char(c) => Some(*c),
+            Item::Part(_) => None,
+        })
+        .collect::<Option<String>>()?;
+    let bounds: Vec<&str> = text.split("..").collect();
+    let (start, end, step) = match bounds.as_slice() {
+        [start, end] => (*start, *end, None),
+        [start, end, step] => (*start, *end, Some(step.parse::<i64>().ok()?.unsigned_abs().max(1))),
+        _ => return None,
+    };
+
+    if let (Ok(a), Ok(b)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        // Leading zeros pad every number to the same width
+        let padded = |s: &str| s.trim_start_matches('-').len() > 1 && s.trim_start_matches('-').starts_with('0');
+        let width = if padded(start) || padded(end) { start.len().max(end.len()) } else { 0 };
+        let step = step.unwrap_or(1) as usize;
+        let numbers: Vec<i64> = if a <= b { (a..=b).step_by(step).collect() } else { (b..=a).rev().step_by(step).collect() };
+        return Some(numbers.into_iter().map(|n| format!("{n:0width$}")).collect());
+    }
+
+    let (mut a, mut b) = (start.chars(), end.chars());
+    let (Some(a), None, Some(b), None) = (a.next(), a.next(), b.next(), b.next()) else { return None };
+    if !a.is_ascii_alphabetic() || !b.is_ascii_alphabetic() {
+        return None;
+    }
+    let step = step.unwrap_or(1) as usize;
+    let letters: Vec<char> = if a <= b { (a..=b).step_by(step).collect() } else { (b..=a).rev().step_by(step).collect() };
+    Some(letters.into_iter().map(String::from).collect())
+}
+
+/// Expand `{a,b}` and `{1..5}` in the unquoted parts of a word
+fn brace_expand(items: &[Item]) -> Vec<Vec<Item>> {
+    for (open, item) in items.iter().enumerate() {
+        if !matches!(item, Item::Char('{')) {
+            continue;
+        }
+        // Find the matching brace and the commas at this level
+        let mut depth = 0;
+        let mut commas = vec![];
+        let mut close = None;
+        for (i, item) in items.iter().enumerate().skip(open + 1) {
+            match item {
+                Item::Char('{') => depth += 1,
+                Item::Char('}') if depth == 0 => {
+                    close = Some(i);
+                    break;
+                },
+                Item::Char('}') => depth -= 1,
+                Item::Char(',') if depth == 0 => commas.push(i),
+                _ => (),
+            }
+        }
+        let Some(close) = close else { continue };
+
+        let alternatives: Vec<Vec<Item>> = if commas.is_empty() {
+            match sequence(&items[open + 1..close]) {
+                Some(words) => words.iter().map(|w| w.chars().map(Item::Char).collect()).collect(),
+                None => continue,
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            bounds.windows(2).map(|w| items[w[0] + 1..w[1]].to_vec()).collect()
+        };
+
+        let prefix = &items[..open];
+        let mut words = vec![];
+        for alternative in alternatives {
+            let mut rest = alternative;
+            rest.extend_from_slice(&items[close + 1..]);
+            for expanded in brace_expand(&rest) {
+                let mut word = prefix.to_vec();
+                word.extend(expanded);
+                words.push(word);
+            }
+        }
+        return words;
+    }
+    vec![items.to_vec()]
+}
+
+/// The home directory of a user from /etc/passwd, or of the current user
+fn home_dir(user: Option<&str>) -> Option<String> {
+    let uid = unsafe { libc::getuid() }.to_string();
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines()
+        .map(|line| line.split(':').collect::<Vec<&str>>())
+        .find(|fields| fields.len() > 5 && match user {
+            Some(user) => fields[0] == user,
+            None => fields[2] == uid,
+        })
+        .map(|fields| fields[5].to_owned())
+}
+
+/// Replace a leading `~` or `~user` with the home directory
+fn tilde_expand(word: &Word) -> Word {
+    let Some(WordPart::Literal(text)) = word.parts.first() else { return word.clone() };
+    let Some(rest) = text.strip_prefix('~') else { return word.clone() };
+    // The user name ends at the first slash and must not be quoted
+    let (user, rest) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None if word.parts.len() == 1 => (rest, ""),
+        None => return word.clone(),
+    };
+    let home = match user {
+        "" => vars::get("HOME").or_else(|| home_dir(None)),
+        user => home_dir(Some(user)),
+    };
+    let Some(home) = home else { return word.clone() };
+
+    let mut parts = vec![WordPart::Quoted(home)];
+    if !rest.is_empty() {
+        parts.push(WordPart::Literal(rest.to_owned()));
+    }
+    parts.extend(word.parts[1..].iter().cloned());
+    Word { parts }
+}
+
+/// Expand words to the fields that become the arguments of a command
+pub fn expand_words(words: &[Word]) -> Result<Vec<String>, String> {
+    let ifs = vars::get("IFS").unwrap_or(DEFAULT_IFS.to_owned());
+    let mut fields = Fields::default();
+    let words = words.iter()
+        .flat_map(|word| brace_expand(&to_items(word)))
+        .map(|items| tilde_expand(&to_word(&items)));
+    for word in words {
+        for part in &word.parts {
+            match part {
+                WordPart::Literal(text) => fields.push_unquoted(text),
+                WordPart::Quoted(text) => fields.push_str(text),
+                WordPart::DoubleQuoted(parts) => {
+                    if parts.is_empty() {
+                        fields.push_str("");
+                    }
+                    for part in parts {
+                        match part {
+                            // "$@" expands to one field per positional parameter
+                            WordPart::Param(p) if p.name == "@" => {
+                                for (i, param) in vars::positional().iter().enumerate() {
+                                    if i > 0 {
+                                        fields.finish();
+                                    }
+                                    fields.push_str(param);
+                                }
+                            },
+                            part => {
+                                let mut out = String::new();
+                                expand_parts(std::slice::from_ref(part), &mut out)?;
+                                fields.push_str(&out);
+                            },
+                        }
+                    }
+                },
+                WordPart::Param(p) => fields.push_split(&param(p)?, &ifs),
+                WordPart::CommandSub(list) => fields.push_split(&exec::substitute(list)?, &ifs),
+                WordPart::Arith(parts) => fields.push_split(&arith(parts)?, &ifs),
+            }
+        }
+        fields.finish();
+    }
+    Ok(fields.fields)
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use crate::pattern;
+
+/// Whether a pattern contains unescaped `*`, `?` or `[`
+pub fn has_glob(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => { chars.next(); },
+            '*' | '?' | '[' => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Remove the backslashes that escape characters in a pattern
+fn unescape(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Expand a pattern to the sorted paths matching it, empty if nothing matches.
+/// Files starting with a dot are only matched by a pattern starting with a dot.
+pub fn glob(pattern: &str) -> Vec<String> {
+    let absolute = pattern.starts_with('/');
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    // A trailing slash only matches directories
+    let dirs_only = pattern.ends_with('/');
+
+    let mut paths = vec![if absolute { "/".to_owned() } else { String::new() }];
+    for component in components {
+        let mut next = vec![];
+        for path in &paths {
+            if !has_glob(component) {
+                next.push(format!("{path}{}/", unescape(component)));
+                continue;
+            }
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            let Ok(entries) = fs::read_dir(dir) else { continue };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') && !component.starts_with('.') && !component.starts_with("\\.") {
+                    continue;
+                }
+                if pattern::matches(component, &name) {
+                    next.push(format!("{path}{name}/"));
+                }
+            }
+        }
+        paths = next;
+    }
+
+    let mut matches: Vec<String> = paths.into_iter()
+        .filter_map(|path| {
+            let trimmed = path.trim_end_matches('/');
+            let path = if trimmed.is_empty() { "/" } else { trimmed };
+            let metadata = fs::metadata(path).ok().or_else(|| fs::symlink_metadata(path).ok())?;
+            match dirs_only {
+                true if metadata.is_dir() => Some(format!("{path}/")),
+                true => None,
+                false => Some(path.to_owned()),
+            }
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A directory with a few files, removed when dropped
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, files: &[&str]) -> Tree {
+            let root = std::env::temp_dir().join(format!("schelp-glob-{name}-{}", std::process::id()));
+            for file in files {
+                let path = root.join(file);
+                match file.strip_suffix('/') {
+                    Some(_) => fs::create_dir_all(&path).unwrap(),
+                    None => {
+                        fs::create_dir_all(path.parent().unwrap()).unwrap();
+                        fs::write(&path, "").unwrap();
+                    },
+                }
+            }
+            Tree(root)
+        }
+
+        /// Glob relative to the tree, the results without the tree's path
+        fn glob(&self, pattern: &str) -> Vec<String> {
+            let root = self.0.to_string_lossy();
+            glob(&format!("{root}/{pattern}")).into_iter()
+                .map(|path| path[root.len() + 1..].to_owned())
+                .collect()
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn has_glob_ignores_escapes() {
+        assert!(has_glob("*.rs"));
+        assert!(has_glob("a[bc]"));
+        assert!(!has_glob("a\\*b"));
+        assert!(!has_glob("plain"));
+    }
+
+    #[test]
+    fn matching_files() {
+        let tree = Tree::new("files", &["a.rs", "b.rs", "c.txt", ".hidden.rs", "src/", "src/lib.rs", "src/main.rs", "docs/"]);
+        assert_eq!(tree.glob("*.rs"), ["a.rs", "b.rs"]);
+        assert_eq!(tree.glob("?.*"), ["a.rs", "b.rs", "c.txt"]);
+        assert_eq!(tree.glob(".*.rs"), [".hidden.rs"]);
+        assert_eq!(tree.glob("*/*.rs"), ["src/lib.rs", "src/main.rs"]);
+        assert_eq!(tree.glob("*/"), ["docs/", "src/"]);
+        assert_eq!(tree.glob("[ab].rs"), ["a.rs", "b.rs"]);
+        assert!(tree.glob("*.py").is_empty());
+    }
+}
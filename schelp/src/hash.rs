@@ -0,0 +1,85 @@
+//! Where the commands that were run were found in PATH, so it isn't searched again.
+//! The table is emptied when PATH changes.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use crate::{is_executable, path_search, vars, BUILD_INS};
+
+#[derive(Default)]
+pub struct Table {
+    /// The PATH the commands were found in
+    path: String,
+    /// The location of each command and how often it was looked up
+    commands: HashMap<String, (PathBuf, usize)>,
+}
+
+static TABLE: LazyLock<Mutex<Table>> = LazyLock::new(|| Mutex::new(Table::default()));
+
+/// Exchange the table with that of another shell
+pub fn swap(table: &mut Table) {
+    std::mem::swap(&mut *TABLE.lock().unwrap(), table);
+}
+
+/// The table, emptied first if PATH changed since it was filled
+fn table() -> std::sync::MutexGuard<'static, Table> {
+    let path = vars::get("PATH").unwrap_or_default();
+    let mut table = TABLE.lock().unwrap();
+    if table.path != path {
+        table.commands.clear();
+        table.path = path;
+    }
+    table
+}
+
+/// Find an executable in PATH, remembering where it was found
+pub fn lookup(cmd: &str) -> Option<PathBuf> {
+    let mut table = table();
+    // Commands that were removed since are searched for again
+    if let Some((path, hits)) = table.commands.get_mut(cmd) && is_executable(path) {
+        *hits += 1;
+        return Some(path.clone());
+    }
+    let path = path_search(&table.path, cmd, true)?;
+    table.commands.insert(cmd.to_owned(), (path.clone(), 1));
+    Some(path)
+}
+
+/// The hash build_in, lists the table without arguments.
+/// `-r` empties it, names are looked up and added to it.
+pub fn hash(args: &[String]) -> i32 {
+    if args.is_empty() {
+        let table = table();
+        if table.commands.is_empty() {
+            println!("hash: hash table empty");
+            return 0;
+        }
+        let mut commands: Vec<(&String, &(PathBuf, usize))> = table.commands.iter().collect();
+        commands.sort();
+        println!("hits\tcommand");
+        for (_, (path, hits)) in commands {
+            println!("{hits:4}\t{}", path.display());
+        }
+        return 0;
+    }
+    let mut code = 0;
+    for arg in args {
+        if arg == "-r" {
+            table().commands.clear();
+            continue;
+        }
+        // Like the shell these don't search PATH
+        if arg.contains('/') || BUILD_INS.contains(&arg.as_str()) {
+            continue;
+        }
+        let path = table().path.clone();
+        match path_search(&path, arg, true) {
+            Some(found) => { table().commands.insert(arg.clone(), (found, 0)); },
+            None => {
+                eprintln!("hash: {arg}: not found");
+                code = 1;
+            },
+        }
+    }
+    code
+}
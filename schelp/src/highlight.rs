@@ -0,0 +1,206 @@
+//! Colours for the line being edited. Commands are green when they exist and red when they don't,
+//! keywords and operators cyan, quoted strings yellow, expansions blue and comments grey.
+//! The line is scanned loosely since it is usually incomplete while it is typed.
+
+use std::path::Path;
+use color::{blue, cyan, green, grey, red, yellow};
+use crate::lexer::{is_name_char, is_name_start};
+use crate::{exec, is_executable, parser, path_search, vars, ALIASES, BUILD_INS};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Command,
+    Unknown,
+    Keyword,
+    String,
+    Expansion,
+    Comment,
+}
+
+/// Characters that end a word and start an operator
+const OPERATOR_CHARS: &str = ";&|()<>";
+
+fn paint(text: &str, style: Style) -> String {
+    match style {
+        Style::Plain => text.to_owned(),
+        Style::Command => green!(text),
+        Style::Unknown => red!(text),
+        Style::Keyword => cyan!(text),
+        Style::String => yellow!(text),
+        Style::Expansion => blue!(text),
+        Style::Comment => grey!(text),
+    }
+}
+
+/// Whether a command would be found: build_ins, aliases, functions and executables
+fn exists(name: &str) -> bool {
+    if BUILD_INS.contains(&name) || ALIASES.lock().unwrap().contains_key(name) || exec::function(name).is_some() {
+        return true;
+    }
+    if name.contains('/') {
+        return is_executable(Path::new(name));
+    }
+    path_search(&vars::get("PATH").unwrap_or_default(), name, true).is_some()
+}
+
+/// The word with quotes and backslashes removed
+fn unquote(word: &str) -> String {
+    let mut out = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            '\'' | '"' => (),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The index after the bracket closing the one at start, or the end of the line
+fn matching(chars: &[char], start: usize, open: char, close: char) -> usize {
+    let mut depth = 0;
+    for (i, c) in chars.iter().enumerate().skip(start) {
+        if *c == open {
+            depth += 1;
+        } else if *c == close {
+            depth -= 1;
+            if depth == 0 {
+                return i + 1;
+            }
+        }
+    }
+    chars.len()
+}
+
+/// The index after an expansion starting with the `$` at start
+fn expansion_end(chars: &[char], start: usize) -> usize {
+    match chars.get(start + 1) {
+        Some('{') => matching(chars, start + 1, '{', '}'),
+        Some('(') => matching(chars, start + 1, '(', ')'),
+        Some(c) if is_name_start(*c) => {
+            let mut end = start + 1;
+            while chars.get(end).is_some_and(|c| is_name_char(*c)) {
+                end += 1;
+            }
+            end
+        },
+        Some(c) if c.is_ascii_digit() || "?#$!@*-".contains(*c) => start + 2,
+        _ => start + 1,
+    }
+}
+
+/// Add text to the spans, joining it with the last span if that has the same style
+fn push(spans: &mut Vec<(String, Style)>, text: &[char], style: Style) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some((last, last_style)) if *last_style == style => last.extend(text),
+        _ => spans.push((text.iter().collect(), style)),
+    }
+}
+
+/// Split the word starting at start into its quoted and expanded parts, returns where it ends
+fn word(chars: &[char], start: usize, spans: &mut Vec<(String, Style)>) -> usize {
+    let mut i = start;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || OPERATOR_CHARS.contains(c) {
+            break;
+        }
+        let end = match c {
+            '\\' => (i + 2).min(chars.len()),
+            '\'' => chars[i + 1..].iter().position(|c| *c == '\'').map_or(chars.len(), |n| i + n + 2),
+            '`' => chars[i + 1..].iter().position(|c| *c == '`').map_or(chars.len(), |n| i + n + 2),
+            '$' => expansion_end(chars, i),
+            '"' => {
+                push(spans, &chars[i..i + 1], Style::String);
+                i += 1;
+                // Expansions inside double quotes are still expanded
+                while i < chars.len() && chars[i] != '"' {
+                    let (end, style) = match chars[i] {
+                        '\\' => ((i + 2).min(chars.len()), Style::String),
+                        '$' => (expansion_end(chars, i), Style::Expansion),
+                        _ => (i + 1, Style::String),
+                    };
+                    push(spans, &chars[i..end], style);
+                    i = end;
+                }
+                (i + 1).min(chars.len())
+            },
+            _ => i + 1,
+        };
+        let style = match c {
+            '\'' | '"' => Style::String,
+            '`' | '$' => Style::Expansion,
+            _ => Style::Plain,
+        };
+        push(spans, &chars[i..end], style);
+        i = end;
+    }
+    i
+}
+
+/// The line with escape sequences colouring its parts, it takes up as many columns as before
+pub fn highlight(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans: Vec<(String, Style)> = vec![];
+    // Whether the next word is a command name
+    let mut command = true;
+    // Whether the next word is the target of a redirection
+    let mut target = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            push(&mut spans, &chars[i..i + 1], Style::Plain);
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            push(&mut spans, &chars[i..], Style::Comment);
+            break;
+        }
+        if OPERATOR_CHARS.contains(c) {
+            let start = i;
+            while i < chars.len() && OPERATOR_CHARS.contains(chars[i]) {
+                i += 1;
+            }
+            let operator = &chars[start..i];
+            if operator.iter().any(|c| *c == '<' || *c == '>') {
+                target = true;
+            } else {
+                command = true;
+            }
+            push(&mut spans, operator, Style::Keyword);
+            continue;
+        }
+
+        let start = i;
+        let mut parts = vec![];
+        i = word(&chars, start, &mut parts);
+        let text: String = chars[start..i].iter().collect();
+        if target || !command {
+            target = false;
+            spans.extend(parts);
+        } else if parser::RESERVED.contains(&text.as_str()) {
+            push(&mut spans, &chars[start..i], Style::Keyword);
+            // These are followed by a name or word instead of a command
+            command = !matches!(text.as_str(), "for" | "case" | "in");
+        } else if vars::split_assignment(&text).is_some() {
+            // Assignments before the command
+            spans.extend(parts);
+        } else if text.contains(['$', '`']) {
+            // Can't tell what the command will be
+            spans.extend(parts);
+            command = false;
+        } else {
+            let style = if exists(&unquote(&text)) { Style::Command } else { Style::Unknown };
+            push(&mut spans, &chars[start..i], style);
+            command = false;
+        }
+    }
+    spans.iter().map(|(text, style)| paint(text, *style)).collect()
+}
@@ -0,0 +1,123 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use crate::vars;
+
+static HISTORY_FILENAME: &str = ".schelp_history";
+
+/// Used when HISTSIZE or HISTFILESIZE are not set
+const DEFAULT_SIZE: usize = 1000;
+
+static HISTORY: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(vec![]));
+
+/// $HISTFILE or ~/.schelp_history
+fn file() -> Option<PathBuf> {
+    if let Some(file) = vars::get("HISTFILE") {
+        return (!file.is_empty()).then(|| PathBuf::from(file));
+    }
+    vars::get("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILENAME))
+}
+
+fn limit(var: &str) -> usize {
+    vars::get(var).and_then(|size| size.parse().ok()).unwrap_or(DEFAULT_SIZE)
+}
+
+/// Read the history file, truncating it if it grew beyond HISTFILESIZE
+pub fn load() {
+    let Some(path) = file() else { return };
+    let Ok(contents) = fs::read_to_string(&path) else { return };
+    let mut lines: Vec<String> = contents.lines().map(|l| l.to_owned()).collect();
+
+    let file_limit = limit("HISTFILESIZE");
+    if lines.len() > file_limit {
+        lines.drain(..lines.len() - file_limit);
+        let _ = fs::write(&path, lines.join("\n") + "\n");
+    }
+
+    let mut history = HISTORY.lock().unwrap();
+    *history = lines;
+    let memory_limit = limit("HISTSIZE");
+    if history.len() > memory_limit {
+        let excess = history.len() - memory_limit;
+        history.drain(..excess);
+    }
+}
+
+/// Add a line to the history and append it to the history file.
+/// Empty lines and repetitions of the previous line are skipped.
+pub fn add(line: &str) {
+    let line = line.trim_end_matches('\n');
+    if line.trim().is_empty() {
+        return;
+    }
+    let mut history = HISTORY.lock().unwrap();
+    if history.last().is_some_and(|last| last == line) {
+        return;
+    }
+    history.push(line.to_owned());
+    let memory_limit = limit("HISTSIZE");
+    if history.len() > memory_limit {
+        let excess = history.len() - memory_limit;
+        history.drain(..excess);
+    }
+    drop(history);
+
+    if let Some(path) = file() && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+pub fn len() -> usize {
+    HISTORY.lock().unwrap().len()
+}
+
+pub fn get(index: usize) -> Option<String> {
+    HISTORY.lock().unwrap().get(index).cloned()
+}
+
+/// Search backwards from `before` for an entry containing `query`
+pub fn search(query: &str, before: usize) -> Option<usize> {
+    let history = HISTORY.lock().unwrap();
+    history[..before.min(history.len())].iter().rposition(|entry| entry.contains(query))
+}
+
+/// The most recent entry that starts with the line being typed, to suggest the rest of it
+pub fn suggest(prefix: &str) -> Option<String> {
+    if prefix.trim().is_empty() {
+        return None;
+    }
+    let history = HISTORY.lock().unwrap();
+    history.iter().rev().find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix)).cloned()
+}
+
+/// The history build_in
+pub fn history(args: &[String]) -> i32 {
+    match args.first().map(|a| a.as_str()) {
+        Some("-c") => {
+            HISTORY.lock().unwrap().clear();
+            if let Some(path) = file() {
+                let _ = fs::write(path, "");
+            }
+            0
+        },
+        Some(n) => {
+            let Ok(n) = n.parse::<usize>() else {
+                eprintln!("history: {n}: numeric argument required");
+                return 1;
+            };
+            let history = HISTORY.lock().unwrap();
+            let start = history.len().saturating_sub(n);
+            for (i, entry) in history.iter().enumerate().skip(start) {
+                println!("{:>5}  {entry}", i + 1);
+            }
+            0
+        },
+        None => {
+            for (i, entry) in HISTORY.lock().unwrap().iter().enumerate() {
+                println!("{:>5}  {entry}", i + 1);
+            }
+            0
+        },
+    }
+}
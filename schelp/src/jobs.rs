@@ -0,0 +1,623 @@
+use libc::{WCOREDUMP, WIFCONTINUED, WIFEXITED, WIFSIGNALED, WIFSTOPPED, WEXITSTATUS, WSTOPSIG, WTERMSIG};
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{LazyLock, Mutex};
+use crate::signal;
+use crate::{time, trap};
+
+pub struct Process {
+    pub pid: i32,
+    /// The raw wait status once the process has terminated
+    status: Option<i32>,
+    /// The signal that stopped the process
+    stopped: Option<i32>,
+    /// Set when something else waited for the process, its status is unknown
+    lost: bool,
+}
+
+impl Process {
+    fn is_running(&self) -> bool {
+        self.status.is_none() && !self.lost
+    }
+}
+
+pub struct Job {
+    pub id: usize,
+    pub pgid: i32,
+    pub command: String,
+    pub processes: Vec<Process>,
+    /// Terminal modes of the job at the time it was stopped
+    tmodes: Option<libc::termios>,
+    /// Whether the user has been told about the current state of the job
+    notified: bool,
+    /// Set when a stopped job was continued by someone other than fg or bg
+    continued: bool,
+}
+
+impl Job {
+    fn is_completed(&self) -> bool {
+        self.processes.iter().all(|p| !p.is_running())
+    }
+
+    fn is_stopped(&self) -> bool {
+        !self.is_completed() && self.processes.iter().all(|p| p.stopped.is_some() || !p.is_running())
+    }
+
+    /// The status of a job is the status of the last process in the pipeline,
+    /// 128 plus the signal when it was killed or stopped by one, 127 when it is unknown
+    fn status(&self) -> Option<i32> {
+        let process = self.processes.last()?;
+        if let Some(signal) = process.stopped {
+            return Some(128 + signal);
+        }
+        if process.lost {
+            return Some(127);
+        }
+        let wstatus = process.status?;
+        match WIFSIGNALED(wstatus) {
+            true => Some(128 + WTERMSIG(wstatus)),
+            false => Some(WEXITSTATUS(wstatus)),
+        }
+    }
+
+    fn state(&self) -> String {
+        if self.is_stopped() {
+            let signal = self.processes.iter().find_map(|p| p.stopped).unwrap_or(libc::SIGTSTP);
+            signal::describe(signal)
+        } else if self.is_completed() {
+            match self.processes.last().and_then(|p| p.status) {
+                Some(wstatus) if WIFSIGNALED(wstatus) => termination(wstatus),
+                _ => match self.status() {
+                    Some(0) => "Done".to_owned(),
+                    Some(code) => format!("Exit {code}"),
+                    None => "Done".to_owned(),
+                },
+            }
+        } else if self.continued {
+            "Continued".to_owned()
+        } else {
+            "Running".to_owned()
+        }
+    }
+}
+
+/// Describe how a process was killed, like "Segmentation fault (core dumped)"
+fn termination(wstatus: i32) -> String {
+    let description = signal::describe(WTERMSIG(wstatus));
+    match WCOREDUMP(wstatus) {
+        true => format!("{description} (core dumped)"),
+        false => description,
+    }
+}
+
+static JOBS: LazyLock<Mutex<Vec<Job>>> = LazyLock::new(|| Mutex::new(vec![]));
+
+/// Set when the shell controls the terminal and can move jobs between the fore- and background
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
+static SHELL_PGID: AtomicI32 = AtomicI32::new(0);
+
+static SHELL_TMODES: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// The jobs of a shell and whether it has job control while it isn't its turn, see [swap]
+#[derive(Default)]
+pub struct State {
+    jobs: Vec<Job>,
+    job_control: bool,
+    pgid: i32,
+    tmodes: Option<libc::termios>,
+}
+
+/// Exchange the job table and the job control of the shell with those of another shell
+pub fn swap(state: &mut State) {
+    std::mem::swap(&mut *JOBS.lock().unwrap(), &mut state.jobs);
+    state.job_control = JOB_CONTROL.swap(state.job_control, Ordering::Relaxed);
+    state.pgid = SHELL_PGID.swap(state.pgid, Ordering::Relaxed);
+    std::mem::swap(&mut *SHELL_TMODES.lock().unwrap(), &mut state.tmodes);
+}
+
+pub fn job_control() -> bool {
+    JOB_CONTROL.load(Ordering::Relaxed)
+}
+
+/// Put the shell in its own process group in the foreground of the terminal.
+/// The job control signals should already be ignored.
+pub fn init() {
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) == 0 {
+            return;
+        }
+
+        // Wait until we are in the foreground
+        loop {
+            let pgrp = libc::getpgrp();
+            let foreground = libc::tcgetpgrp(libc::STDIN_FILENO);
+            if foreground == -1 {
+                eprintln!("schelp: no job control: {}", std::io::Error::last_os_error());
+                return;
+            }
+            if foreground == pgrp {
+                break;
+            }
+            libc::kill(-pgrp, libc::SIGTTIN);
+        }
+
+        let pid = libc::getpid();
+        // This fails if we are a session leader, which is fine
+        libc::setpgid(pid, pid);
+        let pgid = libc::getpgrp();
+        if libc::tcsetpgrp(libc::STDIN_FILENO, pgid) == -1 {
+            eprintln!("schelp: no job control: {}", std::io::Error::last_os_error());
+            return;
+        }
+        SHELL_PGID.store(pgid, Ordering::Relaxed);
+
+        let mut tmodes = MaybeUninit::uninit();
+        if libc::tcgetattr(libc::STDIN_FILENO, tmodes.as_mut_ptr()) == 0 {
+            *SHELL_TMODES.lock().unwrap() = Some(tmodes.assume_init());
+        }
+    }
+    JOB_CONTROL.store(true, Ordering::Relaxed);
+}
+
+/// Forget the jobs of the parent in a forked subshell, which has no job control
+pub fn subshell() {
+    JOB_CONTROL.store(false, Ordering::Relaxed);
+    JOBS.lock().unwrap().clear();
+}
+
+/// Set up a freshly forked child of a job, pgid is 0 for the first process
+pub fn child_setup(pgid: i32, foreground: bool) {
+    if !job_control() {
+        return;
+    }
+    unsafe {
+        let pid = libc::getpid();
+        let pgid = if pgid == 0 { pid } else { pgid };
+        libc::setpgid(pid, pgid);
+        if foreground {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        }
+    }
+}
+
+/// Register a forked pipeline, returns the job id
+pub fn add(pgid: i32, pids: Vec<i32>, command: String) -> usize {
+    let mut jobs = JOBS.lock().unwrap();
+    let id = jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+    jobs.push(Job {
+        id,
+        pgid,
+        command,
+        processes: pids.into_iter().map(|pid| Process { pid, status: None, stopped: None, lost: false }).collect(),
+        tmodes: None,
+        notified: true,
+        continued: false,
+    });
+    id
+}
+
+/// Record a status change reported by waitpid
+fn mark_process_status(pid: i32, wstatus: i32) {
+    let mut jobs = JOBS.lock().unwrap();
+    for job in jobs.iter_mut() {
+        if let Some(process) = job.processes.iter_mut().find(|p| p.pid == pid) {
+            if WIFSTOPPED(wstatus) {
+                process.stopped = Some(WSTOPSIG(wstatus));
+                job.continued = false;
+            } else if WIFCONTINUED(wstatus) {
+                // fg and bg already forgot that it was stopped
+                if process.stopped.take().is_some() {
+                    job.continued = true;
+                    job.notified = false;
+                }
+                return;
+            } else {
+                process.status = Some(wstatus);
+            }
+            if job.is_stopped() || job.is_completed() {
+                job.notified = false;
+            }
+            return;
+        }
+    }
+}
+
+/// Wait for a status change of a child. wait4 is used instead of waitpid
+/// so the time keyword gets the resource usage of the children that terminated.
+/// Only the shell's own children are waited for, others belong to whoever embeds the shell.
+fn wait_child(pid: i32, flags: i32) -> (i32, i32) {
+    let mut wstatus = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    let pid = unsafe { libc::wait4(pid, &mut wstatus, flags | libc::WUNTRACED | libc::WCONTINUED, &mut usage) };
+    if pid > 0 && (WIFEXITED(wstatus) || WIFSIGNALED(wstatus)) {
+        time::record(&usage);
+    }
+    (pid, wstatus)
+}
+
+/// The processes of all jobs that haven't terminated
+fn running() -> Vec<i32> {
+    JOBS.lock().unwrap().iter()
+        .flat_map(|j| j.processes.iter().filter(|p| p.is_running()).map(|p| p.pid))
+        .collect()
+}
+
+/// Give up on a process that something else already waited for
+fn mark_lost(pid: i32) {
+    let mut jobs = JOBS.lock().unwrap();
+    for job in jobs.iter_mut() {
+        if let Some(process) = job.processes.iter_mut().find(|p| p.pid == pid) {
+            eprintln!("schelp: {}: the status of process {pid} was lost", job.command);
+            process.lost = true;
+            process.stopped = None;
+            job.notified = false;
+        }
+    }
+}
+
+/// Collect status changes of children without blocking
+fn update_status() {
+    for pid in running() {
+        loop {
+            let (reaped, wstatus) = wait_child(pid, libc::WNOHANG);
+            if reaped == -1 && std::io::Error::last_os_error().raw_os_error() == Some(libc::ECHILD) {
+                mark_lost(pid);
+            }
+            if reaped <= 0 {
+                break;
+            }
+            mark_process_status(pid, wstatus);
+            if WIFEXITED(wstatus) || WIFSIGNALED(wstatus) {
+                break;
+            }
+        }
+    }
+}
+
+/// Wait for a job to either stop or complete, a process at a time
+fn wait_for_job(id: usize) {
+    loop {
+        let pid = {
+            let jobs = JOBS.lock().unwrap();
+            match jobs.iter().find(|j| j.id == id) {
+                Some(job) if job.is_stopped() || job.is_completed() => return,
+                // A stopped process only reports again once it continues
+                Some(job) => match job.processes.iter().find(|p| p.is_running() && p.stopped.is_none()) {
+                    Some(process) => process.pid,
+                    None => return,
+                },
+                None => return,
+            }
+        };
+        let (reaped, wstatus) = wait_child(pid, 0);
+        if reaped == -1 {
+            let error = std::io::Error::last_os_error();
+            match error.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::ECHILD) => {
+                    mark_lost(pid);
+                    continue;
+                },
+                _ => {
+                    eprintln!("schelp: wait4: {error}");
+                    return;
+                },
+            }
+        }
+        mark_process_status(pid, wstatus);
+    }
+}
+
+/// Run a job in the foreground and return its status once it stops or completes
+pub fn foreground(id: usize, cont: bool) -> Option<i32> {
+    let (pgid, tmodes) = {
+        let mut jobs = JOBS.lock().unwrap();
+        let job = jobs.iter_mut().find(|j| j.id == id)?;
+        job.notified = true;
+        (job.pgid, job.tmodes.take())
+    };
+
+    if job_control() {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+            if let Some(tmodes) = tmodes {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &tmodes);
+            }
+        }
+    }
+    if cont {
+        continue_job(id);
+    }
+
+    wait_for_job(id);
+
+    // Take back the terminal
+    if job_control() {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, SHELL_PGID.load(Ordering::Relaxed));
+            let mut jobs = JOBS.lock().unwrap();
+            if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+                let mut tmodes = MaybeUninit::uninit();
+                if libc::tcgetattr(libc::STDIN_FILENO, tmodes.as_mut_ptr()) == 0 {
+                    job.tmodes = Some(tmodes.assume_init());
+                }
+            }
+            if let Some(tmodes) = *SHELL_TMODES.lock().unwrap() {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &tmodes);
+            }
+        }
+    }
+
+    let mut jobs = JOBS.lock().unwrap();
+    let index = jobs.iter().position(|j| j.id == id)?;
+    let job = &mut jobs[index];
+    if job.is_stopped() {
+        println!();
+        println!("[{}]+  {:<24}{}", job.id, job.state(), job.command);
+        job.notified = true;
+        return job.status();
+    }
+
+    for process in &job.processes {
+        if let Some(wstatus) = process.status && WIFSIGNALED(wstatus) && WTERMSIG(wstatus) != libc::SIGPIPE {
+            if WTERMSIG(wstatus) == libc::SIGINT {
+                // The user pressed ctrl-C, move past the ^C
+                // and stop whatever list or loop started the job
+                println!();
+                trap::interrupt();
+                break;
+            }
+            eprintln!("{}: {}", job.command, termination(wstatus));
+        }
+    }
+    let status = job.status();
+    jobs.remove(index);
+    status
+}
+
+/// Send SIGCONT to a stopped job
+fn continue_job(id: usize) {
+    let mut jobs = JOBS.lock().unwrap();
+    if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+        for process in job.processes.iter_mut() {
+            process.stopped = None;
+        }
+        job.continued = false;
+        unsafe { libc::kill(-job.pgid, libc::SIGCONT) };
+    }
+}
+
+/// Print state changes of background jobs and forget completed ones.
+/// Called before printing the prompt.
+pub fn notify() {
+    update_status();
+    let mut jobs = JOBS.lock().unwrap();
+    let current = jobs.last().map(|j| j.id);
+    for job in jobs.iter_mut() {
+        if !job.notified {
+            let mark = if Some(job.id) == current { '+' } else { '-' };
+            println!("[{}]{mark}  {:<24}{}", job.id, job.state(), job.command);
+            job.notified = true;
+            job.continued = false;
+        }
+    }
+    jobs.retain(|j| !j.is_completed());
+}
+
+/// Ids of all jobs in the table
+pub fn ids() -> Vec<usize> {
+    JOBS.lock().unwrap().iter().map(|j| j.id).collect()
+}
+
+/// Ids and commands of all jobs in the table
+pub fn list() -> Vec<(usize, String)> {
+    JOBS.lock().unwrap().iter().map(|j| (j.id, j.command.clone())).collect()
+}
+
+/// Resolve a job spec like %1, %%, %+, %- or %name to a job id
+fn resolve(spec: Option<&String>) -> Result<usize, String> {
+    let jobs = JOBS.lock().unwrap();
+    let spec = match spec {
+        Some(spec) => spec.as_str(),
+        None => "%+",
+    };
+    let job = match spec {
+        "%%" | "%+" => jobs.last(),
+        "%-" => jobs.iter().rev().nth(1),
+        _ => {
+            let Some(name) = spec.strip_prefix('%') else {
+                // A pid instead of a job spec
+                let pid: i32 = spec.parse().map_err(|_| format!("{spec}: no such job"))?;
+                return jobs.iter()
+                    .find(|j| j.processes.iter().any(|p| p.pid == pid))
+                    .map(|j| j.id)
+                    .ok_or(format!("{spec}: no such job"));
+            };
+            match name.parse::<usize>() {
+                Ok(id) => jobs.iter().find(|j| j.id == id),
+                Err(_) => jobs.iter().find(|j| j.command.starts_with(name)),
+            }
+        },
+    };
+    job.map(|j| j.id).ok_or(format!("{spec}: no such job"))
+}
+
+/// The ids to pass to kill(2) for the job a spec like %1 refers to.
+/// That is the process group with job control, otherwise the processes of the job.
+pub fn kill_ids(spec: &str) -> Result<Vec<i32>, String> {
+    let id = resolve(Some(&spec.to_owned()))?;
+    let jobs = JOBS.lock().unwrap();
+    let job = jobs.iter().find(|j| j.id == id).ok_or(format!("{spec}: no such job"))?;
+    Ok(match job_control() {
+        true => vec![-job.pgid],
+        false => job.processes.iter().filter(|p| p.is_running()).map(|p| p.pid).collect(),
+    })
+}
+
+pub fn jobs(args: &[String]) -> i32 {
+    update_status();
+    let list_pids = args.iter().any(|a| a == "-l");
+    let mut jobs = JOBS.lock().unwrap();
+    let count = jobs.len();
+    for (i, job) in jobs.iter_mut().enumerate() {
+        let mark = match count - i {
+            1 => '+',
+            2 => '-',
+            _ => ' ',
+        };
+        if list_pids {
+            println!("[{}]{mark}  {} {:<24}{}", job.id, job.pgid, job.state(), job.command);
+        } else {
+            println!("[{}]{mark}  {:<24}{}", job.id, job.state(), job.command);
+        }
+        job.notified = true;
+    }
+    jobs.retain(|j| !j.is_completed());
+    0
+}
+
+pub fn fg(args: &[String]) -> Option<i32> {
+    if !job_control() {
+        eprintln!("fg: no job control");
+        return Some(1);
+    }
+    let id = match resolve(args.first()) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("fg: {e}");
+            return Some(1);
+        },
+    };
+    if let Some(job) = JOBS.lock().unwrap().iter().find(|j| j.id == id) {
+        println!("{}", job.command);
+    }
+    foreground(id, true)
+}
+
+pub fn bg(args: &[String]) -> i32 {
+    if !job_control() {
+        eprintln!("bg: no job control");
+        return 1;
+    }
+    let id = match resolve(args.first()) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("bg: {e}");
+            return 1;
+        },
+    };
+    continue_job(id);
+    if let Some(job) = JOBS.lock().unwrap().iter_mut().find(|j| j.id == id) {
+        println!("[{}]+ {} &", job.id, job.command);
+        job.notified = true;
+    }
+    0
+}
+
+/// Wait for the given jobs, or all of them.
+/// Returns the status of the last job waited for.
+pub fn wait(args: &[String]) -> Option<i32> {
+    let ids: Vec<usize> = if args.is_empty() {
+        JOBS.lock().unwrap().iter().map(|j| j.id).collect()
+    } else {
+        let mut ids = vec![];
+        for arg in args {
+            match resolve(Some(arg)) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    eprintln!("wait: {e}");
+                    return Some(127);
+                },
+            }
+        }
+        ids
+    };
+
+    let mut status = Some(0);
+    for id in ids {
+        wait_for_job(id);
+        let mut jobs = JOBS.lock().unwrap();
+        if let Some(index) = jobs.iter().position(|j| j.id == id) {
+            status = jobs[index].status();
+            if jobs[index].is_completed() {
+                jobs.remove(index);
+            }
+        }
+    }
+    if args.is_empty() {
+        Some(0)
+    } else {
+        status
+    }
+}
+
+pub fn disown(args: &[String]) -> i32 {
+    let id = match resolve(args.first()) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("disown: {e}");
+            return 1;
+        },
+    };
+    JOBS.lock().unwrap().retain(|j| j.id != id);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wait statuses as the kernel reports them
+    fn exited(code: i32) -> i32 {
+        code << 8
+    }
+
+    fn killed(sig: i32, core: bool) -> i32 {
+        sig | if core { 0x80 } else { 0 }
+    }
+
+    fn job(processes: Vec<(Option<i32>, Option<i32>)>) -> Job {
+        Job {
+            id: 1,
+            pgid: 1,
+            command: "test".to_owned(),
+            processes: processes.into_iter().map(|(status, stopped)| Process { pid: 1, status, stopped, lost: false }).collect(),
+            tmodes: None,
+            notified: false,
+            continued: false,
+        }
+    }
+
+    #[test]
+    fn exit_statuses() {
+        let done = job(vec![(Some(exited(1)), None), (Some(exited(0)), None)]);
+        assert_eq!(done.status(), Some(0));
+        assert_eq!(done.state(), "Done");
+        let failed = job(vec![(Some(exited(3)), None)]);
+        assert_eq!(failed.status(), Some(3));
+        assert_eq!(failed.state(), "Exit 3");
+        assert_eq!(job(vec![(None, None)]).state(), "Running");
+    }
+
+    #[test]
+    fn signal_statuses() {
+        let terminated = job(vec![(Some(killed(libc::SIGTERM, false)), None)]);
+        assert_eq!(terminated.status(), Some(128 + libc::SIGTERM));
+        assert_eq!(terminated.state(), "Terminated");
+        let crashed = job(vec![(Some(killed(libc::SIGSEGV, true)), None)]);
+        assert_eq!(crashed.status(), Some(128 + libc::SIGSEGV));
+        assert_eq!(crashed.state(), "Segmentation fault (core dumped)");
+    }
+
+    #[test]
+    fn stopped_jobs() {
+        let stopped = job(vec![(Some(exited(0)), None), (None, Some(libc::SIGTTIN))]);
+        assert!(stopped.is_stopped());
+        assert_eq!(stopped.status(), Some(128 + libc::SIGTTIN));
+        assert_eq!(stopped.state(), "Stopped (tty input)");
+        let mut continued = job(vec![(None, None)]);
+        continued.continued = true;
+        assert_eq!(continued.state(), "Continued");
+    }
+}
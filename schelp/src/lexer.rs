@@ -0,0 +1,832 @@
+use std::fmt;
+use crate::ast::{Param, ParamOp, Word, WordPart};
+use crate::redirect::RedirectKind;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Offset of the offending character in the source
+    pub pos: usize,
+    /// The input ended before the command was complete, more lines could fix it
+    pub incomplete: bool,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, pos: usize) -> ParseError {
+        ParseError { message: message.into(), pos, incomplete: false }
+    }
+
+    pub fn incomplete(message: impl Into<String>, pos: usize) -> ParseError {
+        ParseError { message: message.into(), pos, incomplete: true }
+    }
+
+    /// Format the error with the offending line and a marker under the column
+    pub fn report(&self, source: &str) -> String {
+        // Errors at the end of the input point just past the last line
+        let mut pos = self.pos;
+        if source.ends_with('\n') {
+            pos = pos.min(source.chars().count() - 1);
+        }
+        let mut line = 1;
+        let mut line_start = 0;
+        for (i, c) in source.chars().enumerate().take(pos) {
+            if c == '\n' {
+                line += 1;
+                line_start = i + 1;
+            }
+        }
+        let column = pos - line_start;
+        let text: String = source.chars().skip(line_start).take_while(|c| *c != '\n').collect();
+        format!("schelp: syntax error at {line}:{}: {}\n{text}\n{}^", column + 1, self.message, " ".repeat(column))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// `|`
+    Pipe,
+    /// `||`
+    OrIf,
+    /// `&`
+    Amp,
+    /// `&&`
+    AndIf,
+    /// `;`
+    Semi,
+    /// `;;`
+    DSemi,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Op::Pipe => "|",
+            Op::OrIf => "||",
+            Op::Amp => "&",
+            Op::AndIf => "&&",
+            Op::Semi => ";",
+            Op::DSemi => ";;",
+            Op::LParen => "(",
+            Op::RParen => ")",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Word(Word),
+    /// A redirection operator with the fd it applies to
+    Redirect {
+        fd: i32,
+        kind: RedirectKind,
+        /// `&>`, redirects both stdout and stderr
+        both: bool,
+    },
+    /// `<<` or `<<-`, the body is read by the parser after the delimiter
+    HereDoc {
+        fd: i32,
+        /// `<<-`, leading tabs are removed from the lines
+        strip_tabs: bool,
+    },
+    Op(Op),
+    Newline,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: usize,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            TokenKind::Word(word) => write!(f, "`{word}'"),
+            TokenKind::Redirect { .. } | TokenKind::HereDoc { .. } => write!(f, "redirection"),
+            TokenKind::Op(op) => write!(f, "`{op}'"),
+            TokenKind::Newline => write!(f, "newline"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+fn is_metachar(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '|' | '&' | ';' | '(' | ')' | '<' | '>')
+}
+
+pub fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Whether the text is a valid variable or function name
+pub fn is_name(name: &str) -> bool {
+    name.starts_with(is_name_start) && name.chars().all(is_name_char)
+}
+
+/// Special parameters like `$?` and `$1`
+fn is_special_param(c: char) -> bool {
+    matches!(c, '?' | '$' | '!' | '#' | '@' | '*' | '-') || c.is_ascii_digit()
+}
+
+/// Collects the parts of a word, merging adjacent text
+#[derive(Default)]
+struct PartsBuilder {
+    parts: Vec<WordPart>,
+    literal: String,
+}
+
+impl PartsBuilder {
+    fn flush(&mut self) {
+        if !self.literal.is_empty() {
+            self.parts.push(WordPart::Literal(std::mem::take(&mut self.literal)));
+        }
+    }
+
+    fn push(&mut self, part: WordPart) {
+        self.flush();
+        self.parts.push(part);
+    }
+
+    fn finish(mut self) -> Vec<WordPart> {
+        self.flush();
+        self.parts
+    }
+}
+
+pub struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    /// Aliases being expanded and where their text ends, to prevent recursion
+    aliases: Vec<(String, usize)>,
+    /// Where the body of the next here-document on the current line starts,
+    /// set once the body of one was read
+    here_doc_start: Option<usize>,
+    /// The input ends in a line continuation, the line after it is still to come
+    continued: bool,
+}
+
+impl Lexer {
+    pub fn new(source: &str) -> Lexer {
+        Lexer::from_chars(source.chars().collect())
+    }
+
+    pub fn from_chars(chars: Vec<char>) -> Lexer {
+        Lexer { chars, pos: 0, aliases: vec![], here_doc_start: None, continued: false }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    /// Insert the expansion of an alias at the current position, returns where it ends
+    pub fn insert_alias(&mut self, name: &str, text: &str) -> usize {
+        self.aliases.retain(|(_, end)| *end >= self.pos);
+        let len = text.chars().count();
+        self.chars.splice(self.pos..self.pos, text.chars());
+        for (_, end) in self.aliases.iter_mut() {
+            *end += len;
+        }
+        self.aliases.push((name.to_owned(), self.pos + len));
+        self.pos + len
+    }
+
+    /// Whether we are inside the expansion of this alias
+    pub fn in_alias(&self, name: &str) -> bool {
+        self.aliases.iter().any(|(alias, end)| alias == name && *end >= self.pos)
+    }
+
+    /// Consume the `()` after the name of a function definition, if it is there
+    pub fn function_parens(&mut self) -> bool {
+        let mut pos = self.pos;
+        let mut expect = ['(', ')'].into_iter().peekable();
+        while let Some(&c) = self.chars.get(pos) {
+            match c {
+                ' ' | '\t' => (),
+                c if expect.peek() == Some(&c) => {
+                    expect.next();
+                    if expect.peek().is_none() {
+                        self.pos = pos + 1;
+                        return true;
+                    }
+                },
+                _ => return false,
+            }
+            pos += 1;
+        }
+        false
+    }
+
+    fn skip_blanks(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t') => self.pos += 1,
+                // Line continuation
+                Some('\\') if self.peek_at(1) == Some('\n') => self.line_continuation(),
+                // Comments run until the end of the line
+                Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                },
+                _ => return,
+            }
+        }
+    }
+
+    /// Skip a backslash and the newline after it
+    fn line_continuation(&mut self) {
+        self.pos += 2;
+        self.continued = self.pos == self.chars.len();
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_blanks();
+        let pos = self.pos;
+        let Some(c) = self.peek() else {
+            if self.continued {
+                return Err(ParseError::incomplete("line continuation at end of input", pos));
+            }
+            return Ok(Token { kind: TokenKind::Eof, pos });
+        };
+
+        let next = self.peek_at(1);
+        let op = |op, len| (TokenKind::Op(op), len);
+        let (kind, len) = match (c, next) {
+            ('\n', _) => {
+                self.here_doc_start = None;
+                (TokenKind::Newline, 1)
+            },
+            ('|', Some('|')) => op(Op::OrIf, 2),
+            ('|', _) => op(Op::Pipe, 1),
+            ('&', Some('&')) => op(Op::AndIf, 2),
+            ('&', Some('>')) => {
+                let append = self.peek_at(2) == Some('>');
+                let kind = if append { RedirectKind::Append } else { RedirectKind::Write };
+                (TokenKind::Redirect { fd: 1, kind, both: true }, if append { 3 } else { 2 })
+            },
+            ('&', _) => op(Op::Amp, 1),
+            (';', Some(';')) => op(Op::DSemi, 2),
+            (';', _) => op(Op::Semi, 1),
+            ('(', _) => op(Op::LParen, 1),
+            (')', _) => op(Op::RParen, 1),
+            ('<' | '>', _) => self.redirect_op(if c == '<' { 0 } else { 1 }),
+            _ => {
+                // Digits directly followed by a redirection are the fd to redirect
+                let digits = self.chars[self.pos..].iter().take_while(|c| c.is_ascii_digit()).count();
+                if digits > 0 && matches!(self.peek_at(digits), Some('<' | '>')) {
+                    let fd: String = self.chars[self.pos..self.pos + digits].iter().collect();
+                    let fd = fd.parse().map_err(|_| ParseError::new("file descriptor out of range", pos))?;
+                    self.pos += digits;
+                    self.redirect_op(fd)
+                } else {
+                    let word = self.read_word()?;
+                    return Ok(Token { kind: TokenKind::Word(word), pos });
+                }
+            },
+        };
+        self.pos += len;
+        Ok(Token { kind, pos })
+    }
+
+    /// The redirection operator at the current position and its length
+    fn redirect_op(&self, fd: i32) -> (TokenKind, usize) {
+        let (kind, len) = match (self.peek(), self.peek_at(1)) {
+            (Some('<'), Some('<')) => match self.peek_at(2) {
+                Some('<') => (RedirectKind::HereString, 3),
+                Some('-') => return (TokenKind::HereDoc { fd, strip_tabs: true }, 3),
+                _ => return (TokenKind::HereDoc { fd, strip_tabs: false }, 2),
+            },
+            (Some('>'), Some('>')) => (RedirectKind::Append, 2),
+            (Some('>'), Some('|')) => (RedirectKind::Write, 2),
+            (Some('<'), Some('>')) => (RedirectKind::ReadWrite, 2),
+            (_, Some('&')) => (RedirectKind::Dup, 2),
+            (Some('<'), _) => (RedirectKind::Read, 1),
+            _ => (RedirectKind::Write, 1),
+        };
+        (TokenKind::Redirect { fd, kind, both: false }, len)
+    }
+
+    /// Read the body of a here-document, from the line after the current one up to the delimiter.
+    /// The lines are removed from the input. Unless the delimiter is quoted the body is expanded.
+    pub fn read_here_doc(&mut self, delimiter: &Word, strip_tabs: bool, pos: usize) -> Result<Word, ParseError> {
+        let unterminated = || ParseError::incomplete("unterminated here-document", pos);
+        let quoted = delimiter.parts.iter().any(|part| !matches!(part, WordPart::Literal(_)));
+        let delimiter: Vec<char> = unquoted(&delimiter.parts).chars().collect();
+
+        // Several here-documents on one line follow each other
+        let start = match self.here_doc_start {
+            Some(start) => start,
+            None => match self.chars[self.pos..].iter().position(|c| *c == '\n') {
+                Some(newline) => self.pos + newline + 1,
+                None => return Err(unterminated()),
+            },
+        };
+        let mut body = String::new();
+        let mut line_start = start;
+        let end = loop {
+            if line_start >= self.chars.len() {
+                return Err(unterminated());
+            }
+            let line_end = self.chars[line_start..].iter().position(|c| *c == '\n')
+                .map_or(self.chars.len(), |i| line_start + i);
+            let mut line = &self.chars[line_start..line_end];
+            if strip_tabs {
+                while let [first, rest @ ..] = line && *first == '\t' {
+                    line = rest;
+                }
+            }
+            let next = (line_end + 1).min(self.chars.len());
+            if line == delimiter.as_slice() {
+                break next;
+            }
+            body.extend(line);
+            body.push('\n');
+            line_start = next;
+        };
+
+        self.chars.drain(start..end);
+        for (_, alias_end) in self.aliases.iter_mut() {
+            if *alias_end >= end {
+                *alias_end -= end - start;
+            } else if *alias_end > start {
+                *alias_end = start;
+            }
+        }
+        self.here_doc_start = Some(start);
+
+        if quoted {
+            return Ok(Word { parts: vec![WordPart::Quoted(body)] });
+        }
+        let parts = Lexer::new(&body).read_here_doc_text()
+            // The body is complete, an unterminated expansion in it is an error
+            .map_err(|e| ParseError::new(e.message, start + e.pos))?;
+        Ok(Word { parts: vec![WordPart::DoubleQuoted(parts)] })
+    }
+
+    /// Read the text of an unquoted here-document into parts, only `$`, `` ` `` and `\` are special
+    fn read_here_doc_text(&mut self) -> Result<Vec<WordPart>, ParseError> {
+        let mut builder = PartsBuilder::default();
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => match self.peek_at(1) {
+                    Some('\n') => self.pos += 2,
+                    Some(c @ ('$' | '`' | '\\')) => {
+                        builder.literal.push(c);
+                        self.pos += 2;
+                    },
+                    _ => {
+                        builder.literal.push('\\');
+                        self.pos += 1;
+                    },
+                },
+                '$' => match self.read_dollar()? {
+                    Some(part) => builder.push(part),
+                    None => {
+                        builder.literal.push('$');
+                        self.pos += 1;
+                    },
+                },
+                '`' => {
+                    let part = self.read_backticks(false)?;
+                    builder.push(part);
+                },
+                c => {
+                    builder.literal.push(c);
+                    self.pos += 1;
+                },
+            }
+        }
+        Ok(builder.finish())
+    }
+
+    fn read_word(&mut self) -> Result<Word, ParseError> {
+        let mut builder = PartsBuilder::default();
+        while let Some(c) = self.peek() {
+            if is_metachar(c) {
+                break;
+            }
+            match c {
+                '\\' => match self.peek_at(1) {
+                    Some('\n') => self.line_continuation(),
+                    Some(c) => {
+                        builder.push(WordPart::Quoted(c.to_string()));
+                        self.pos += 2;
+                    },
+                    None => {
+                        builder.literal.push('\\');
+                        self.pos += 1;
+                    },
+                },
+                '\'' => {
+                    let text = self.read_single_quoted()?;
+                    builder.push(WordPart::Quoted(text));
+                },
+                '"' => {
+                    let parts = self.read_double_quoted()?;
+                    builder.push(WordPart::DoubleQuoted(parts));
+                },
+                '`' => {
+                    let part = self.read_backticks(false)?;
+                    builder.push(part);
+                },
+                '$' => match self.read_dollar()? {
+                    Some(part) => builder.push(part),
+                    None => {
+                        builder.literal.push('$');
+                        self.pos += 1;
+                    },
+                },
+                _ => {
+                    builder.literal.push(c);
+                    self.pos += 1;
+                },
+            }
+        }
+        Ok(Word { parts: builder.finish() })
+    }
+
+    fn read_single_quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                Some('\'') => break,
+                Some(c) => text.push(c),
+                None => return Err(ParseError::incomplete("unterminated single quote", start)),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        Ok(text)
+    }
+
+    fn read_double_quoted(&mut self) -> Result<Vec<WordPart>, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut builder = PartsBuilder::default();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::incomplete("unterminated double quote", start)),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(builder.finish());
+                },
+                Some('\\') => {
+                    // Inside double quotes a backslash only escapes a few characters
+                    match self.peek_at(1) {
+                        Some('\n') => self.pos += 2,
+                        Some(c @ ('$' | '`' | '"' | '\\')) => {
+                            builder.literal.push(c);
+                            self.pos += 2;
+                        },
+                        _ => {
+                            builder.literal.push('\\');
+                            self.pos += 1;
+                        },
+                    }
+                },
+                Some('$') => match self.read_dollar()? {
+                    Some(part) => builder.push(part),
+                    None => {
+                        builder.literal.push('$');
+                        self.pos += 1;
+                    },
+                },
+                Some('`') => {
+                    let part = self.read_backticks(true)?;
+                    builder.push(part);
+                },
+                Some(c) => {
+                    builder.literal.push(c);
+                    self.pos += 1;
+                },
+            }
+        }
+    }
+
+    /// Read a backtick substitution, backslashes escape `$`, `` ` `` and `\\`
+    /// and also `"` inside double quotes
+    fn read_backticks(&mut self, quoted: bool) -> Result<WordPart, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::incomplete("unterminated backtick substitution", start)),
+                Some('`') => break,
+                Some('\\') => match self.peek_at(1) {
+                    Some(c @ ('$' | '`' | '\\')) => {
+                        text.push(c);
+                        self.pos += 1;
+                    },
+                    Some('"') if quoted => {
+                        text.push('"');
+                        self.pos += 1;
+                    },
+                    _ => text.push('\\'),
+                },
+                Some(c) => text.push(c),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        let list = crate::parser::parse(&text).map_err(|e| {
+            // Point into the original text, which differs after escapes
+            ParseError { pos: start + 1 + e.pos.min(text.chars().count()), ..e }
+        })?;
+        Ok(WordPart::CommandSub(list))
+    }
+
+    /// Read the text of `$((expression))` into parts, parameters in it are expanded
+    fn read_arith(&mut self) -> Result<WordPart, ParseError> {
+        let start = self.pos;
+        self.pos += 3;
+        let mut builder = PartsBuilder::default();
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::incomplete("unterminated arithmetic expansion", start)),
+                Some(')') if depth == 0 && self.peek_at(1) == Some(')') => {
+                    self.pos += 2;
+                    return Ok(WordPart::Arith(builder.finish()));
+                },
+                Some('$') => match self.read_dollar()? {
+                    Some(part) => builder.push(part),
+                    None => {
+                        builder.literal.push('$');
+                        self.pos += 1;
+                    },
+                },
+                Some(c) => {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => (),
+                    }
+                    builder.literal.push(c);
+                    self.pos += 1;
+                },
+            }
+        }
+    }
+
+    /// Read an expansion starting with `$`, None if the `$` is just a character
+    fn read_dollar(&mut self) -> Result<Option<WordPart>, ParseError> {
+        let start = self.pos;
+        match self.peek_at(1) {
+            Some('(') if self.peek_at(2) == Some('(') => self.read_arith().map(Some),
+            Some('(') => {
+                let (list, len) = crate::parser::parse_substitution(&self.chars[self.pos + 2..])
+                    .map_err(|e| ParseError { pos: e.pos + self.pos + 2, ..e })?;
+                self.pos += 2 + len;
+                Ok(Some(WordPart::CommandSub(list)))
+            },
+            Some('{') => {
+                self.pos += 2;
+                self.read_braced(start).map(|param| Some(WordPart::Param(param)))
+            },
+            Some(c) if is_special_param(c) => {
+                self.pos += 2;
+                Ok(Some(WordPart::Param(Param::new(&c.to_string()))))
+            },
+            Some(c) if is_name_start(c) => {
+                self.pos += 1;
+                let name = self.read_name();
+                Ok(Some(WordPart::Param(Param::new(&name))))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn read_name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.peek() && is_name_char(c) {
+            name.push(c);
+            self.pos += 1;
+        }
+        name
+    }
+
+    /// Read the inside of `${...}` up to and including the closing brace
+    fn read_braced(&mut self, start: usize) -> Result<Param, ParseError> {
+        let unterminated = || ParseError::incomplete("unterminated parameter expansion", start);
+        let bad = || ParseError::new("bad substitution", start);
+
+        // `${#NAME}` is the length, `${#}` alone the number of positional parameters
+        let length = self.peek() == Some('#') && self.peek_at(1).is_some_and(|c| c != '}');
+        if length {
+            self.pos += 1;
+        }
+        let name = match self.peek() {
+            Some(c) if is_name_start(c) => self.read_name(),
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(c) = self.peek() && c.is_ascii_digit() {
+                    digits.push(c);
+                    self.pos += 1;
+                }
+                digits
+            },
+            Some(c) if is_special_param(c) => {
+                self.pos += 1;
+                c.to_string()
+            },
+            Some(_) => return Err(bad()),
+            None => return Err(unterminated()),
+        };
+
+        let colon = self.peek() == Some(':');
+        let op = match (self.peek_at(colon as usize), colon) {
+            (Some('}'), false) => {
+                self.pos += 1;
+                let op = length.then_some(ParamOp::Length);
+                return Ok(Param { name, braced: true, op });
+            },
+            (None, _) => return Err(unterminated()),
+            _ if length => return Err(bad()),
+            (Some(c @ ('-' | '=' | '?' | '+')), colon) => {
+                self.pos += 1 + colon as usize;
+                let word = self.read_braced_word(start)?;
+                match c {
+                    '-' => ParamOp::Default { word, colon },
+                    '=' => ParamOp::Assign { word, colon },
+                    '?' => ParamOp::Error { word, colon },
+                    _ => ParamOp::Alternative { word, colon },
+                }
+            },
+            (Some(c @ ('#' | '%')), false) => {
+                let longest = self.peek_at(1) == Some(c);
+                self.pos += 1 + longest as usize;
+                let pattern = self.read_braced_word(start)?;
+                if c == '#' {
+                    ParamOp::RemovePrefix { pattern, longest }
+                } else {
+                    ParamOp::RemoveSuffix { pattern, longest }
+                }
+            },
+            _ => return Err(bad()),
+        };
+        Ok(Param { name, braced: true, op: Some(op) })
+    }
+
+    /// Read the word after the operator of `${NAME:-word}` up to and including the closing brace
+    fn read_braced_word(&mut self, start: usize) -> Result<Word, ParseError> {
+        let mut builder = PartsBuilder::default();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::incomplete("unterminated parameter expansion", start)),
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Word { parts: builder.finish() });
+                },
+                Some('\\') => {
+                    self.pos += 1;
+                    if let Some(c) = self.peek() {
+                        builder.push(WordPart::Quoted(c.to_string()));
+                        self.pos += 1;
+                    }
+                },
+                Some('\'') => {
+                    let text = self.read_single_quoted()?;
+                    builder.push(WordPart::Quoted(text));
+                },
+                Some('"') => {
+                    let parts = self.read_double_quoted()?;
+                    builder.push(WordPart::DoubleQuoted(parts));
+                },
+                Some('`') => {
+                    let part = self.read_backticks(false)?;
+                    builder.push(part);
+                },
+                Some('$') => match self.read_dollar()? {
+                    Some(part) => builder.push(part),
+                    None => {
+                        builder.literal.push('$');
+                        self.pos += 1;
+                    },
+                },
+                Some(c) => {
+                    builder.literal.push(c);
+                    self.pos += 1;
+                },
+            }
+        }
+    }
+}
+
+/// The text of a word with the quotes removed, used for the delimiter of a here-document
+fn unquoted(parts: &[WordPart]) -> String {
+    parts.iter()
+        .map(|part| match part {
+            WordPart::Literal(text) | WordPart::Quoted(text) => text.clone(),
+            WordPart::DoubleQuoted(parts) => unquoted(parts),
+            part => Word { parts: vec![part.clone()] }.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kinds of all tokens up to the end of the input
+    fn tokens(source: &str) -> Vec<TokenKind> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = vec![];
+        loop {
+            match lexer.next_token().unwrap().kind {
+                TokenKind::Eof => return tokens,
+                kind => tokens.push(kind),
+            }
+        }
+    }
+
+    fn word(parts: Vec<WordPart>) -> TokenKind {
+        TokenKind::Word(Word { parts })
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(tokens(r#"a'b c'"d $x"\ e"#), vec![word(vec![
+            WordPart::Literal("a".to_owned()),
+            WordPart::Quoted("b c".to_owned()),
+            WordPart::DoubleQuoted(vec![WordPart::Literal("d ".to_owned()), WordPart::Param(Param::new("x"))]),
+            WordPart::Quoted(" ".to_owned()),
+            WordPart::Literal("e".to_owned()),
+        ])]);
+        // Single quotes keep backslashes and dollars, double quotes only escape some characters
+        assert_eq!(tokens(r#"'\$x' "\a\$""#), vec![
+            word(vec![WordPart::Quoted("\\$x".to_owned())]),
+            word(vec![WordPart::DoubleQuoted(vec![WordPart::Literal("\\a$".to_owned())])]),
+        ]);
+        // Operators inside quotes are part of the word
+        assert_eq!(tokens("'a|b;c'"), vec![word(vec![WordPart::Quoted("a|b;c".to_owned())])]);
+    }
+
+    #[test]
+    fn unterminated_quotes_are_incomplete() {
+        for source in ["'abc", "\"abc", "$(echo", "`echo", "${x"] {
+            let mut lexer = Lexer::new(source);
+            let e = lexer.next_token().unwrap_err();
+            assert!(e.incomplete, "{source}: {e}");
+        }
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(tokens("a|b||c&&d&;;"), vec![
+            word(vec![WordPart::Literal("a".to_owned())]),
+            TokenKind::Op(Op::Pipe),
+            word(vec![WordPart::Literal("b".to_owned())]),
+            TokenKind::Op(Op::OrIf),
+            word(vec![WordPart::Literal("c".to_owned())]),
+            TokenKind::Op(Op::AndIf),
+            word(vec![WordPart::Literal("d".to_owned())]),
+            TokenKind::Op(Op::Amp),
+            TokenKind::Op(Op::DSemi),
+        ]);
+        assert_eq!(tokens("2>&1 <<-"), vec![
+            TokenKind::Redirect { fd: 2, kind: RedirectKind::Dup, both: false },
+            word(vec![WordPart::Literal("1".to_owned())]),
+            TokenKind::HereDoc { fd: 0, strip_tabs: true },
+        ]);
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_name("_a1"));
+        assert!(!is_name("1a"));
+        assert!(!is_name(""));
+        assert!(!is_name("a-b"));
+    }
+
+    #[test]
+    fn report() {
+        let e = ParseError::new("unexpected token", 10);
+        assert_eq!(e.report("echo\necho )"), "schelp: syntax error at 2:6: unexpected token\necho )\n     ^");
+    }
+}
@@ -0,0 +1,374 @@
+//! The schelp shell as a library. A [Shell] runs commands in the state it keeps,
+//! [parse] only checks them.
+#![feature(str_split_whitespace_remainder)]
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::sync::{LazyLock, Mutex};
+
+static ALIASES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static BUILD_INS: &[&str] = &[
+    "clear", "=", "alias", "cd", "exit", "export", "unset", "env",
+    "jobs", "fg", "bg", "wait", "disown", "trap", "history",
+    "break", "continue", "return", "local", "shift", "set",
+    "pwd", "echo", "printf", "read", "test", "[", "source", ".", "exec",
+    "type", "command", "unalias", "umask", "ulimit", "kill", "true", "false", "hash",
+    "pushd", "popd", "dirs",
+];
+
+mod signal;
+mod redirect;
+mod vars;
+mod jobs;
+mod trap;
+mod editor;
+mod history;
+mod complete;
+pub mod ast;
+mod lexer;
+mod parser;
+mod expand;
+mod exec;
+mod pattern;
+mod glob;
+mod arith;
+mod options;
+mod builtins;
+mod test;
+mod prompt;
+mod hash;
+mod time;
+mod dirs;
+mod highlight;
+mod shell;
+
+pub use lexer::ParseError;
+pub use shell::{Input, Shell};
+
+/// Parse commands without running them. Aliases belong to a shell, none are expanded.
+pub fn parse(source: &str) -> Result<ast::List, ParseError> {
+    shell::between_turns(|| parser::parse(source))
+}
+
+/// Read a line from stdin.
+/// Unlike [io::Stdin::read_line] this returns when interrupted by SIGINT.
+fn read_line(line: &mut String) -> io::Result<usize> {
+    let mut buf = vec![];
+    loop {
+        let mut byte = 0u8;
+        let r = unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        match r {
+            0 => break,
+            1 => {
+                buf.push(byte);
+                if byte == b'\n' {
+                    break;
+                }
+            },
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted && !trap::pending(libc::SIGINT) {
+                    // Some other trapped signal
+                    run_traps();
+                    continue;
+                }
+                return Err(e);
+            },
+        }
+    }
+    line.push_str(&String::from_utf8_lossy(&buf));
+    Ok(buf.len())
+}
+
+/// Run a line of input, used for traps
+fn run_string(line: &str) {
+    let mut status = None;
+    run_source(line, &mut status);
+}
+
+/// Run the lines of a file or string, commands may span multiple lines
+fn run_source(source: &str, status: &mut Option<i32>) {
+    let mut pending = String::new();
+    for line in source.lines() {
+        pending.push_str(line);
+        pending.push('\n');
+        if parser::parse(&pending).is_err_and(|e| e.incomplete) {
+            continue;
+        }
+        run_line(&std::mem::take(&mut pending), status);
+        // Stopped by return or ctrl-C
+        if exec::unwinding() {
+            return;
+        }
+    }
+    // Reports the unfinished command
+    if !pending.is_empty() {
+        run_line(&pending, status);
+    }
+}
+
+/// The source build_in, runs a file in the current shell.
+/// Extra arguments are the positional parameters while it runs.
+fn source(args: &[String]) -> i32 {
+    let Some((file, args)) = args.split_first() else {
+        eprintln!("source: filename argument required");
+        return 2;
+    };
+    // A name without a slash is looked up in PATH before the current directory
+    let path = match file.contains('/') {
+        true => None,
+        false => path_search(&vars::get("PATH").unwrap_or_default(), file, false),
+    };
+    let path = path.unwrap_or_else(|| PathBuf::from(file));
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("source: {file}: {e}");
+            return 1;
+        },
+    };
+    let saved = (!args.is_empty()).then(|| vars::set_positional(args.to_vec()));
+    let mut status = None;
+    exec::source(|| run_source(&text, &mut status));
+    if let Some(saved) = saved {
+        vars::set_positional(saved);
+    }
+    status.unwrap_or(0)
+}
+
+/// Parse and run a line, status is updated when anything was executed
+fn run_line(line: &str, status: &mut Option<i32>) {
+    match parser::parse(line) {
+        Ok(list) if list.items.is_empty() => (),
+        Ok(list) => {
+            *status = exec::run(&list);
+            save_status(*status);
+        },
+        Err(e) => {
+            eprintln!("{}", e.report(line));
+            *status = Some(2);
+            save_status(*status);
+        },
+    }
+}
+
+/// Run the commands of signals that were trapped, $status is preserved
+fn run_traps() {
+    for cmd in trap::take_pending() {
+        let status = vars::get("status");
+        run_string(&cmd);
+        match status {
+            Some(status) => vars::set("status", &status),
+            None => vars::unset("status"),
+        }
+    }
+}
+
+fn exit_shell(code: i32) -> ! {
+    if let Some(cmd) = trap::exit_trap() {
+        run_string(&cmd);
+    }
+    io::stdout().flush().ok();
+    std::process::exit(code);
+}
+
+// either set or unset the status variable
+fn save_status(code: Option<i32>) {
+    match code {
+        Some(code) => vars::set("status", &code.to_string()),
+        None => vars::unset("status"),
+    }
+}
+
+// Returns a code on execution.
+// Return none on no execution.
+fn build_in(cmd: &str, args: &[String]) -> Option<i32> {
+    match cmd {
+        "clear" => {
+            // https://en.wikipedia.org/wiki/ANSI_escape_code#CSI_(Control_Sequence_Introducer)_sequences
+            print!("\x1b[2J"); //clear
+            print!("\x1b[0;0H"); //cursor
+            Some(0)
+        },
+        "=" => {
+            if args.len() < 2{
+                println!("Invalid use of =");
+                Some(1)
+            } else {
+                vars::set(&args[0], &args[1..].join(" "));
+                Some(0)
+            }
+        },
+        "alias" => {
+            if args.len() < 2{
+                for alias in ALIASES.lock().unwrap().iter() {
+                    println!("{} = {}", alias.0, alias.1);
+                }
+                Some(0)
+            } else {
+                ALIASES.lock().unwrap().insert(args[0].to_owned(), args[1..].join(" "));
+                Some(0)
+            }
+        },
+        "cd" => Some(dirs::cd(args)),
+        "pushd" => Some(dirs::pushd(args)),
+        "popd" => Some(dirs::popd(args)),
+        "dirs" => Some(dirs::dirs(args)),
+        "exit" => {
+            let code = match args.first() {
+                Some(arg) => match arg.parse::<i32>() {
+                    Ok(code) => code & 0xff,
+                    Err(_) => {
+                        eprintln!("exit: {arg}: numeric argument required");
+                        2
+                    },
+                },
+                // The status of the last command
+                None => vars::get("?").and_then(|s| s.parse().ok()).unwrap_or(0),
+            };
+            Some(exec::exit(code))
+        },
+        "export" => {
+            if args.is_empty() {
+                for (name, value) in vars::exported() {
+                    println!("export {name}=\"{value}\"");
+                }
+                return Some(0)
+            }
+            let mut code = 0;
+            for arg in args {
+                let (name, value) = match arg.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (arg.as_str(), None),
+                };
+                if lexer::is_name(name) {
+                    vars::export(name, value);
+                } else {
+                    eprintln!("export: {name}: not a valid identifier");
+                    code = 1;
+                }
+            }
+            Some(code)
+        },
+        "unset" => {
+            for arg in args {
+                vars::unset(arg);
+            }
+            Some(0)
+        },
+        "jobs" => Some(jobs::jobs(args)),
+        "fg" => jobs::fg(args),
+        "bg" => Some(jobs::bg(args)),
+        "wait" => jobs::wait(args),
+        "disown" => Some(jobs::disown(args)),
+        "trap" => Some(trap::trap(args)),
+        "history" => Some(history::history(args)),
+        "set" => Some(options::set(args)),
+        "break" | "continue" => Some(exec::loop_control(cmd, args)),
+        "return" => Some(exec::return_from_function(args)),
+        "local" => {
+            let mut code = 0;
+            for arg in args {
+                let (name, value) = match arg.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (arg.as_str(), None),
+                };
+                if !lexer::is_name(name) {
+                    eprintln!("local: `{arg}': not a valid identifier");
+                    code = 1;
+                    continue;
+                }
+                if let Err(e) = vars::local(name, value) {
+                    eprintln!("local: {e}");
+                    return Some(1);
+                }
+            }
+            Some(code)
+        },
+        "shift" => {
+            let n = match args.first().map(|n| n.parse::<usize>()) {
+                None => 1,
+                Some(Ok(n)) => n,
+                Some(Err(_)) => {
+                    eprintln!("shift: {}: numeric argument required", args[0]);
+                    return Some(1);
+                },
+            };
+            if vars::shift(n) { Some(0) } else { Some(1) }
+        },
+        "true" => Some(0),
+        "false" => Some(1),
+        "pwd" => Some(dirs::pwd(args)),
+        "echo" => Some(builtins::echo(args)),
+        "printf" => Some(builtins::printf(args)),
+        "read" => Some(builtins::read(args)),
+        "test" | "[" => Some(test::test(cmd, args)),
+        "source" | "." => Some(source(args)),
+        "type" => Some(builtins::describe(args)),
+        "command" => builtins::command(args),
+        "umask" => Some(builtins::umask(args)),
+        "ulimit" => Some(builtins::ulimit(args)),
+        "kill" => Some(builtins::kill(args)),
+        "hash" => Some(hash::hash(args)),
+        "unalias" => {
+            let mut aliases = ALIASES.lock().unwrap();
+            if args.first().is_some_and(|a| a == "-a") {
+                aliases.clear();
+                return Some(0);
+            }
+            let mut code = 0;
+            for arg in args {
+                if aliases.remove(arg).is_none() {
+                    eprintln!("unalias: {arg}: not found");
+                    code = 1;
+                }
+            }
+            Some(code)
+        },
+        "env" => {
+            // env NAME=value... cmd runs cmd with the extra variables
+            let mut assignments = vec![];
+            let mut args = args.iter();
+            let mut next = args.next();
+            while let Some(arg) = next && let Some((name, value)) = vars::split_assignment(arg) {
+                assignments.push((name.to_owned(), value.to_owned()));
+                next = args.next();
+            }
+            match next {
+                Some(cmd) => {
+                    let command = exec::Command {
+                        assignments,
+                        cmd: cmd.clone(),
+                        args: args.cloned().collect(),
+                        redirects: vec![],
+                    };
+                    exec::run_command(command).or(Some(127))
+                },
+                None => {
+                    for var in vars::environ(&assignments) {
+                        println!("{}", var.to_string_lossy());
+                    }
+                    Some(0)
+                },
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Search the colon separated directories of a PATH for a file, which has to be executable if asked.
+/// Empty entries stand for the current directory.
+fn path_search(path: &str, cmd: &str, executable: bool) -> Option<PathBuf> {
+    path.split(':')
+        .map(|dir| Path::new(if dir.is_empty() { "." } else { dir }).join(cmd))
+        .find(|file| file.is_file() && (!executable || is_executable(file)))
+}
+
+fn is_executable(file: &Path) -> bool {
+    let Ok(path) = std::ffi::CString::new(file.as_os_str().as_encoded_bytes()) else {
+        return false;
+    };
+    file.is_file() && unsafe { libc::access(path.as_ptr(), libc::X_OK) } == 0
+}
@@ -0,0 +1,95 @@
+use std::sync::Mutex;
+use crate::vars;
+
+/// Options that can be turned on with `set -u` or `set -o name`, some only have a name
+const OPTIONS: &[(Option<char>, &str)] = &[
+    (Some('e'), "errexit"),
+    (None, "ignoreeof"),
+    (Some('u'), "nounset"),
+    (Some('x'), "xtrace"),
+];
+
+/// Names of the options that are on
+static ENABLED: Mutex<Vec<&str>> = Mutex::new(vec![]);
+
+/// Exchange the options in use with those of another shell
+pub fn swap(enabled: &mut Vec<&'static str>) {
+    std::mem::swap(&mut *ENABLED.lock().unwrap(), enabled);
+}
+
+pub fn enabled(name: &str) -> bool {
+    ENABLED.lock().unwrap().contains(&name)
+}
+
+fn enable(name: &'static str, on: bool) {
+    let mut enabled = ENABLED.lock().unwrap();
+    enabled.retain(|n| *n != name);
+    if on {
+        enabled.push(name);
+    }
+}
+
+/// The flags of all options that are on, the value of `$-`
+pub fn flags() -> String {
+    let enabled = ENABLED.lock().unwrap();
+    OPTIONS.iter()
+        .filter(|(_, name)| enabled.contains(name))
+        .filter_map(|(flag, _)| *flag)
+        .collect()
+}
+
+/// The set build_in, turns options on with `-` and off with `+`.
+/// Arguments after the options, or after `--`, become the positional parameters.
+pub fn set(args: &[String]) -> i32 {
+    if args.is_empty() {
+        for name in vars::names() {
+            if let Some(value) = vars::get(&name) {
+                println!("{name}={value}");
+            }
+        }
+        return 0;
+    }
+
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.peek() {
+        if arg.as_str() == "--" {
+            args.next();
+            vars::set_positional(args.cloned().collect());
+            return 0;
+        }
+        let on = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _ => break,
+        };
+        let arg = args.next().unwrap();
+        if arg == "-o" || arg == "+o" {
+            let Some(name) = args.next() else {
+                // Without a name the options are listed
+                for (_, name) in OPTIONS {
+                    println!("{name:<15}{}", if enabled(name) { "on" } else { "off" });
+                }
+                return 0;
+            };
+            match OPTIONS.iter().find(|(_, n)| n == name) {
+                Some((_, name)) => enable(name, on),
+                None => {
+                    eprintln!("set: {name}: invalid option name");
+                    return 2;
+                },
+            }
+            continue;
+        }
+        for c in arg.chars().skip(1) {
+            let Some((_, name)) = OPTIONS.iter().find(|(flag, _)| *flag == Some(c)) else {
+                eprintln!("set: {}{c}: invalid option", if on { '-' } else { '+' });
+                return 2;
+            };
+            enable(name, on);
+        }
+    }
+    if args.peek().is_some() {
+        vars::set_positional(args.cloned().collect());
+    }
+    0
+}
@@ -0,0 +1,516 @@
+use crate::ast::*;
+use crate::lexer::{is_name, Lexer, Op, ParseError, Token, TokenKind};
+use crate::redirect::RedirectKind;
+use crate::ALIASES;
+
+/// Words that are only special at the start of a command
+pub const RESERVED: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "while", "until", "for", "in",
+    "do", "done", "case", "esac", "{", "}", "!", "time",
+];
+
+/// Parse a complete program
+pub fn parse(source: &str) -> Result<List, ParseError> {
+    let mut parser = Parser::new(source)?;
+    let result = parser.list(&[]).and_then(|list| match parser.current.kind {
+        TokenKind::Eof => Ok(list),
+        _ => Err(parser.unexpected()),
+    });
+    // Running into the end of the input means the command is not finished yet
+    result.map_err(|mut e| {
+        e.incomplete |= parser.current.kind == TokenKind::Eof;
+        e
+    })
+}
+
+/// Parse the list of a `$(list)` substitution, the chars start after the `$(`.
+/// Returns the list and the number of chars up to and including the `)`.
+pub fn parse_substitution(chars: &[char]) -> Result<(List, usize), ParseError> {
+    let mut parser = Parser::from_lexer(Lexer::from_chars(chars.to_vec()))?;
+    let result = parser.list(&[]).and_then(|list| match parser.current.kind {
+        TokenKind::Op(Op::RParen) => Ok(list),
+        _ => Err(parser.unexpected()),
+    });
+    let list = result.map_err(|mut e| {
+        e.incomplete |= parser.current.kind == TokenKind::Eof;
+        e
+    })?;
+    // Aliases may have grown the text, but everything after the `)` is untouched
+    let rest = parser.lexer.len() - parser.current.pos - 1;
+    Ok((list, chars.len() - rest))
+}
+
+struct Parser {
+    lexer: Lexer,
+    current: Token,
+    /// Set to the end of an expanded alias ending in a space, the word after it is checked for aliases as well
+    check_alias: Option<usize>,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Parser, ParseError> {
+        Parser::from_lexer(Lexer::new(source))
+    }
+
+    fn from_lexer(mut lexer: Lexer) -> Result<Parser, ParseError> {
+        let current = lexer.next_token()?;
+        Ok(Parser { lexer, current, check_alias: None })
+    }
+
+    fn advance(&mut self) -> Result<Token, ParseError> {
+        let next = self.lexer.next_token()?;
+        Ok(std::mem::replace(&mut self.current, next))
+    }
+
+    fn unexpected(&self) -> ParseError {
+        ParseError::new(format!("unexpected {}", self.current), self.current.pos)
+    }
+
+    fn is_op(&self, op: Op) -> bool {
+        self.current.kind == TokenKind::Op(op)
+    }
+
+    /// Whether the current token is the given reserved word
+    fn is_reserved(&self, word: &str) -> bool {
+        match &self.current.kind {
+            TokenKind::Word(w) => w.as_literal() == Some(word),
+            _ => false,
+        }
+    }
+
+    fn expect_reserved(&mut self, word: &str) -> Result<(), ParseError> {
+        if !self.is_reserved(word) {
+            return Err(ParseError::new(format!("expected `{word}' but found {}", self.current), self.current.pos));
+        }
+        self.advance()?;
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) -> Result<(), ParseError> {
+        while self.current.kind == TokenKind::Newline {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the current word with its alias, if it is one.
+    /// Only called for words in the position of a command name.
+    fn expand_alias(&mut self) -> Result<(), ParseError> {
+        loop {
+            let TokenKind::Word(word) = &self.current.kind else { return Ok(()) };
+            let Some(name) = word.as_literal() else { return Ok(()) };
+            if self.lexer.in_alias(name) {
+                return Ok(());
+            }
+            let Some(expansion) = ALIASES.lock().unwrap().get(name).cloned() else { return Ok(()) };
+            let name = name.to_owned();
+            let end = self.lexer.insert_alias(&name, &expansion);
+            self.check_alias = expansion.ends_with(' ').then_some(end);
+            self.advance()?;
+        }
+    }
+
+    /// A list of and-or lists, ends at EOF, `)`, `;;` or one of the given reserved words
+    fn list(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        let mut list = List::default();
+        loop {
+            self.skip_newlines()?;
+            self.expand_alias()?;
+            match &self.current.kind {
+                TokenKind::Eof | TokenKind::Op(Op::RParen | Op::DSemi) => break,
+                TokenKind::Word(word) if word.as_literal().is_some_and(|w| terminators.contains(&w)) => break,
+                _ => (),
+            }
+            let mut and_or = self.and_or()?;
+            match self.current.kind {
+                TokenKind::Op(Op::Semi) | TokenKind::Newline => {
+                    self.advance()?;
+                },
+                TokenKind::Op(Op::Amp) => {
+                    and_or.background = true;
+                    self.advance()?;
+                },
+                _ => {
+                    list.items.push(and_or);
+                    break;
+                },
+            }
+            list.items.push(and_or);
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = vec![];
+        loop {
+            let connector = match self.current.kind {
+                TokenKind::Op(Op::AndIf) => Connector::And,
+                TokenKind::Op(Op::OrIf) => Connector::Or,
+                _ => break,
+            };
+            self.advance()?;
+            self.skip_newlines()?;
+            rest.push((connector, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest, background: false })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        self.expand_alias()?;
+        let time = match self.is_reserved("time") {
+            true => {
+                self.advance()?;
+                match self.is_reserved("-p") {
+                    true => {
+                        self.advance()?;
+                        Some(TimeFormat::Posix)
+                    },
+                    false => Some(TimeFormat::Default),
+                }
+            },
+            false => None,
+        };
+        // A bare `time` reports the time of nothing, like bash
+        if time.is_some() && matches!(self.current.kind, TokenKind::Newline | TokenKind::Eof
+            | TokenKind::Op(Op::Semi | Op::Amp | Op::AndIf | Op::OrIf | Op::RParen | Op::DSemi)) {
+            return Ok(Pipeline { time, negated: false, commands: vec![] });
+        }
+        let negated = self.is_reserved("!");
+        if negated {
+            self.advance()?;
+        }
+        let mut commands = vec![self.command()?];
+        while self.is_op(Op::Pipe) {
+            self.advance()?;
+            self.skip_newlines()?;
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline { time, negated, commands })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        self.expand_alias()?;
+        let compound = match &self.current.kind {
+            TokenKind::Op(Op::LParen) => {
+                self.advance()?;
+                let list = self.list(&[])?;
+                if !self.is_op(Op::RParen) {
+                    return Err(ParseError::new(format!("expected `)' but found {}", self.current), self.current.pos));
+                }
+                self.advance()?;
+                CompoundCommand::Subshell(list)
+            },
+            TokenKind::Word(word) => match word.as_literal() {
+                Some("{") => {
+                    self.advance()?;
+                    let list = self.list(&["}"])?;
+                    self.expect_reserved("}")?;
+                    CompoundCommand::BraceGroup(list)
+                },
+                Some("if") => self.if_clause()?,
+                Some("while") => self.while_clause(false)?,
+                Some("until") => self.while_clause(true)?,
+                Some("for") => self.for_clause()?,
+                Some("case") => self.case_clause()?,
+                Some(reserved) if RESERVED.contains(&reserved) => return Err(self.unexpected()),
+                Some(name) if is_name(name) && self.lexer.function_parens() => {
+                    let name = name.to_owned();
+                    return self.function_definition(name);
+                },
+                _ => return Ok(Command::Simple(self.simple_command()?)),
+            },
+            _ => return Ok(Command::Simple(self.simple_command()?)),
+        };
+        let mut redirects = vec![];
+        while let TokenKind::Redirect { .. } | TokenKind::HereDoc { .. } = self.current.kind {
+            self.redirect(&mut redirects)?;
+        }
+        Ok(Command::Compound(compound, redirects))
+    }
+
+    /// The body of `name() body` after the parentheses
+    fn function_definition(&mut self, name: String) -> Result<Command, ParseError> {
+        self.advance()?;
+        self.skip_newlines()?;
+        let pos = self.current.pos;
+        let body = self.command()?;
+        if !matches!(body, Command::Compound(..)) {
+            return Err(ParseError::new("the body of a function must be a compound command", pos));
+        }
+        Ok(Command::Function(name, Box::new(body)))
+    }
+
+    fn redirect(&mut self, redirects: &mut Vec<Redirection>) -> Result<(), ParseError> {
+        let token = self.advance()?;
+        if let TokenKind::HereDoc { fd, strip_tabs } = token.kind {
+            let TokenKind::Word(delimiter) = &self.current.kind else {
+                return Err(ParseError::new("missing here-document delimiter", self.current.pos));
+            };
+            // The body has to be read before the lexer moves past the end of the line
+            let target = self.lexer.read_here_doc(delimiter, strip_tabs, token.pos)?;
+            let delimiter = format!("{}{delimiter}", if strip_tabs { "-" } else { "" });
+            self.advance()?;
+            redirects.push(Redirection { fd, kind: RedirectKind::HereDoc, target, delimiter: Some(delimiter) });
+            return Ok(());
+        }
+        let TokenKind::Redirect { fd, kind, both } = token.kind else {
+            unreachable!()
+        };
+        let TokenKind::Word(target) = self.advance()?.kind else {
+            return Err(ParseError::new("missing redirection target", self.current.pos));
+        };
+        redirects.push(Redirection { fd, kind, target, delimiter: None });
+        if both {
+            redirects.push(Redirection { fd: 2, kind: RedirectKind::Dup, target: Word::literal("1"), delimiter: None });
+        }
+        Ok(())
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+        loop {
+            match &self.current.kind {
+                TokenKind::Word(word) => {
+                    if command.words.is_empty() && let Some(assignment) = as_assignment(word) {
+                        command.assignments.push(assignment);
+                        self.advance()?;
+                        continue;
+                    }
+                    if let Some(end) = self.check_alias && self.current.pos >= end {
+                        self.check_alias = None;
+                        self.expand_alias()?;
+                        continue;
+                    }
+                    let TokenKind::Word(word) = self.advance()?.kind else { unreachable!() };
+                    command.words.push(word);
+                },
+                TokenKind::Redirect { .. } | TokenKind::HereDoc { .. } => self.redirect(&mut command.redirects)?,
+                _ => break,
+            }
+        }
+        self.check_alias = None;
+        if command.assignments.is_empty() && command.words.is_empty() && command.redirects.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(command)
+    }
+
+    fn if_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_reserved("if")?;
+        let mut branches = vec![];
+        let mut otherwise = None;
+        loop {
+            let condition = self.list(&["then"])?;
+            self.expect_reserved("then")?;
+            let body = self.list(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            if self.is_reserved("elif") {
+                self.advance()?;
+                continue;
+            }
+            if self.is_reserved("else") {
+                self.advance()?;
+                otherwise = Some(self.list(&["fi"])?);
+            }
+            self.expect_reserved("fi")?;
+            return Ok(CompoundCommand::If { branches, otherwise });
+        }
+    }
+
+    fn while_clause(&mut self, until: bool) -> Result<CompoundCommand, ParseError> {
+        self.advance()?;
+        let condition = self.list(&["do"])?;
+        let body = self.do_group()?;
+        Ok(CompoundCommand::While { condition, body, until })
+    }
+
+    /// `do list done`
+    fn do_group(&mut self) -> Result<List, ParseError> {
+        self.expect_reserved("do")?;
+        let body = self.list(&["done"])?;
+        self.expect_reserved("done")?;
+        Ok(body)
+    }
+
+    fn for_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_reserved("for")?;
+        let var = match &self.current.kind {
+            TokenKind::Word(word) if word.as_literal().is_some_and(is_name) => word.as_literal().unwrap().to_owned(),
+            _ => return Err(ParseError::new(format!("expected a variable name but found {}", self.current), self.current.pos)),
+        };
+        self.advance()?;
+        self.skip_newlines()?;
+
+        let mut words = None;
+        if self.is_reserved("in") {
+            self.advance()?;
+            let mut list = vec![];
+            while let TokenKind::Word(_) = self.current.kind {
+                let TokenKind::Word(word) = self.advance()?.kind else { unreachable!() };
+                list.push(word);
+            }
+            words = Some(list);
+            match self.current.kind {
+                TokenKind::Op(Op::Semi) | TokenKind::Newline => { self.advance()?; },
+                _ => return Err(self.unexpected()),
+            }
+        } else if self.is_op(Op::Semi) {
+            self.advance()?;
+        }
+        self.skip_newlines()?;
+        let body = self.do_group()?;
+        Ok(CompoundCommand::For { var, words, body })
+    }
+
+    fn case_clause(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.expect_reserved("case")?;
+        let TokenKind::Word(word) = self.advance()?.kind else {
+            return Err(ParseError::new("expected a word after `case'", self.current.pos));
+        };
+        self.skip_newlines()?;
+        self.expect_reserved("in")?;
+
+        let mut arms = vec![];
+        loop {
+            self.skip_newlines()?;
+            if self.is_reserved("esac") {
+                self.advance()?;
+                return Ok(CompoundCommand::Case { word, arms });
+            }
+            if self.is_op(Op::LParen) {
+                self.advance()?;
+            }
+            let mut patterns = vec![];
+            loop {
+                let TokenKind::Word(pattern) = self.advance()?.kind else {
+                    return Err(ParseError::new("expected a pattern", self.current.pos));
+                };
+                patterns.push(pattern);
+                if self.is_op(Op::Pipe) {
+                    self.advance()?;
+                    continue;
+                }
+                if self.is_op(Op::RParen) {
+                    self.advance()?;
+                    break;
+                }
+                return Err(self.unexpected());
+            }
+            let body = self.list(&["esac"])?;
+            arms.push(CaseArm { patterns, body });
+            if self.is_op(Op::DSemi) {
+                self.advance()?;
+            } else if !self.is_reserved("esac") {
+                return Err(self.unexpected());
+            }
+        }
+    }
+}
+
+/// Split a word like `NAME=value` into an assignment
+fn as_assignment(word: &Word) -> Option<Assignment> {
+    let Some(WordPart::Literal(first)) = word.parts.first() else { return None };
+    let (name, value) = first.split_once('=')?;
+    if !is_name(name) {
+        return None;
+    }
+    let mut parts = vec![];
+    if !value.is_empty() {
+        parts.push(WordPart::Literal(value.to_owned()));
+    }
+    parts.extend(word.parts[1..].iter().cloned());
+    Some(Assignment { name: name.to_owned(), value: Word { parts } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first simple command of the list
+    fn first(list: &List) -> &SimpleCommand {
+        match &list.items[0].first.commands[0] {
+            Command::Simple(simple) => simple,
+            command => panic!("not a simple command: {command}"),
+        }
+    }
+
+    #[test]
+    fn here_docs() {
+        let list = parse("cat <<EOF; echo after\nhello $x\nEOF\necho next\n").unwrap();
+        assert_eq!(list.items.len(), 3);
+        let redirect = &first(&list).redirects[0];
+        assert_eq!(redirect.kind, RedirectKind::HereDoc);
+        // The body is expanded like a double quoted word
+        assert_eq!(redirect.target.parts, vec![WordPart::DoubleQuoted(vec![
+            WordPart::Literal("hello ".to_owned()),
+            WordPart::Param(Param::new("x")),
+            WordPart::Literal("\n".to_owned()),
+        ])]);
+
+        // A quoted delimiter turns expansions off
+        let list = parse("cat <<'EOF'\n$x `y`\nEOF\n").unwrap();
+        assert_eq!(first(&list).redirects[0].target.parts, vec![WordPart::Quoted("$x `y`\n".to_owned())]);
+
+        // <<- removes leading tabs, also before the delimiter
+        let list = parse("cat <<-EOF\n\tindented\n\tEOF\n").unwrap();
+        assert_eq!(first(&list).redirects[0].target.parts, vec![
+            WordPart::DoubleQuoted(vec![WordPart::Literal("indented\n".to_owned())]),
+        ]);
+
+        assert!(parse("cat <<EOF\nno end").unwrap_err().incomplete);
+    }
+
+    #[test]
+    fn incomplete_commands() {
+        for source in ["if true; then", "echo |", "while true; do echo", "case x in", "f() {", "echo &&"] {
+            assert!(parse(source).unwrap_err().incomplete, "{source}");
+        }
+        for source in ["fi", "echo )", "done", "; ;", "if; then fi"] {
+            assert!(!parse(source).unwrap_err().incomplete, "{source}");
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let list = parse("! a | b && c || d &\ne").unwrap();
+        assert_eq!(list.items.len(), 2);
+        let and_or = &list.items[0];
+        assert!(and_or.background);
+        assert!(and_or.first.negated);
+        assert_eq!(and_or.first.commands.len(), 2);
+        assert_eq!(and_or.rest.iter().map(|(connector, _)| *connector).collect::<Vec<_>>(), [Connector::And, Connector::Or]);
+
+        let list = parse("X=1 Y=$z cmd arg >out").unwrap();
+        let simple = first(&list);
+        assert_eq!(simple.assignments.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(), ["X", "Y"]);
+        assert_eq!(simple.words.len(), 2);
+        assert_eq!(simple.redirects[0].kind, RedirectKind::Write);
+
+        // Assignments after the command name are arguments
+        assert!(first(&parse("cmd X=1").unwrap()).assignments.is_empty());
+        // Reserved words are only special at the start of a command
+        assert_eq!(first(&parse("echo if then fi").unwrap()).words.len(), 4);
+    }
+
+    #[test]
+    fn time() {
+        let list = parse("time -p a | b").unwrap();
+        assert_eq!(list.items[0].first.time, Some(TimeFormat::Posix));
+        assert_eq!(list.items[0].first.commands.len(), 2);
+        for source in ["time", "time -p; echo", "time && echo", "(time)"] {
+            assert!(parse(source).is_ok(), "{source}");
+        }
+        assert!(parse("time |").is_err());
+        // Only special at the start of a pipeline
+        assert_eq!(first(&parse("echo time").unwrap()).words.len(), 2);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for source in ["a && b | c", "if a; then b; else c; fi", "for i in 1 2; do echo $i; done", "f() { echo \"$@\"; }"] {
+            let printed = parse(source).unwrap().to_string();
+            assert_eq!(parse(&printed).unwrap(), parse(source).unwrap(), "{source} printed as {printed}");
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Shell pattern matching as used by `case`.
+//! `*` matches any string, `?` any character and `[...]` a set of characters,
+//! a backslash makes the next character match literally.
+
+/// Whether the whole text matches the pattern
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*` when the rest fails to match
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+                continue;
+            },
+            Some('?') => {
+                p += 1;
+                t += 1;
+                continue;
+            },
+            Some('[') => {
+                if let Some((matched, len)) = match_bracket(&pattern[p..], text[t]) {
+                    if matched {
+                        p += len;
+                        t += 1;
+                        continue;
+                    }
+                } else if text[t] == '[' {
+                    // An unterminated bracket is an ordinary character
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+            },
+            Some('\\') if p + 1 < pattern.len() && pattern[p + 1] == text[t] => {
+                p += 2;
+                t += 1;
+                continue;
+            },
+            // An escaped character that doesn't match, the backslash isn't literal
+            Some('\\') if p + 1 < pattern.len() => (),
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            },
+            _ => (),
+        }
+        // Mismatch, let the last `*` swallow one more character
+        match backtrack {
+            Some((bp, bt)) => {
+                p = bp;
+                t = bt + 1;
+                backtrack = Some((bp, bt + 1));
+            },
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a character against a bracket expression at the start of the pattern.
+/// Returns whether it matched and the length of the expression, None if it is not terminated.
+fn match_bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut start = *pattern.get(i)?;
+        // A `]` right at the start is part of the set
+        if start == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if start == '\\' {
+            i += 1;
+            start = *pattern.get(i)?;
+        }
+        if pattern.get(i + 1) == Some(&'-') && let Some(&end) = pattern.get(i + 2) && end != ']' {
+            matched |= start <= c && c <= end;
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rs.bak"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(matches("?", "é"));
+        assert!(!matches("?", ""));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("abc", "abcd"));
+    }
+
+    #[test]
+    fn brackets() {
+        assert!(matches("[abc]", "b"));
+        assert!(!matches("[abc]", "d"));
+        assert!(matches("[a-z]x", "qx"));
+        assert!(matches("[!a-z]", "Q"));
+        assert!(matches("[^a-z]", "1"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+        // An unterminated bracket is an ordinary character
+        assert!(matches("[ab", "[ab"));
+        assert!(!matches("[ab", "a"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+        assert!(matches("a\\?", "a?"));
+        assert!(!matches("\\a", "\\"));
+        assert!(matches("\\[a]", "[a]"));
+    }
+}
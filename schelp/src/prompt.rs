@@ -0,0 +1,144 @@
+//! Expansion of the backslash escapes in PS1, PS2 and RPS1.
+//!
+//! `\u` user, `\h`/`\H` short/full hostname, `\w`/`\W` full/last part of the cwd,
+//! `\?` last status, `\j` number of jobs, `\t`/`\T`/`\A` time, `\d` date,
+//! `\$` the `-u`/`-r` prompt, `\n` newline, `\e` escape and `\\` a backslash.
+//! `\c{red}` colours the text up to the next `\c{}` with the color crate,
+//! `\c{status}` is green after success and red after failure.
+
+use std::ffi::CStr;
+use color::{blue, green, red, yellow};
+use crate::{dirs, jobs, vars};
+
+/// The working directory and the `-u`/`-r` prompt, coloured by the last status
+pub const DEFAULT_PS1: &str = "\\w \\c{status}\\$\\c{} ";
+
+fn user() -> String {
+    if let Some(user) = vars::get("USER") {
+        return user;
+    }
+    let uid = unsafe { libc::getuid() };
+    std::fs::read_to_string("/etc/passwd").ok()
+        .and_then(|passwd| passwd.lines()
+            .map(|line| line.split(':').collect::<Vec<&str>>())
+            .find(|fields| fields.len() > 2 && fields[2] == uid.to_string())
+            .map(|fields| fields[0].to_owned()))
+        .unwrap_or(uid.to_string())
+}
+
+fn hostname() -> String {
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } == -1 {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(name.nodename.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+/// The working directory with the home directory abbreviated to `~`
+fn cwd() -> String {
+    dirs::abbreviate(&dirs::current())
+}
+
+fn local_time() -> libc::tm {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    tm
+}
+
+fn colour(name: &str, text: String, status: Option<i32>) -> String {
+    match name {
+        "red" => red!(text),
+        "green" => green!(text),
+        "blue" => blue!(text),
+        "yellow" => yellow!(text),
+        "status" => match status {
+            Some(0) => green!(text),
+            Some(_) => red!(text),
+            None => text,
+        },
+        _ => text,
+    }
+}
+
+/// Expand the escapes of a prompt. status is that of the last command,
+/// symbol what `\$` expands to.
+pub fn expand(prompt: &str, status: Option<i32>, symbol: &str) -> String {
+    const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let mut out = String::new();
+    // The colour being applied and where its text starts in out
+    let mut coloured: Option<(String, usize)> = None;
+    let mut chars = prompt.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => out += &user(),
+            Some('h') => out += hostname().split('.').next().unwrap_or_default(),
+            Some('H') => out += &hostname(),
+            Some('w') => out += &cwd(),
+            Some('W') => {
+                let cwd = cwd();
+                out += match cwd.as_str() {
+                    "/" | "~" => &cwd,
+                    _ => cwd.rsplit('/').next().unwrap_or_default(),
+                };
+            },
+            Some('?') => out += &status.unwrap_or(0).to_string(),
+            Some('j') => out += &jobs::ids().len().to_string(),
+            Some('t') => {
+                let tm = local_time();
+                out += &format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec);
+            },
+            Some('T') => {
+                let tm = local_time();
+                let hour = if tm.tm_hour % 12 == 0 { 12 } else { tm.tm_hour % 12 };
+                out += &format!("{hour:02}:{:02}:{:02}", tm.tm_min, tm.tm_sec);
+            },
+            Some('A') => {
+                let tm = local_time();
+                out += &format!("{:02}:{:02}", tm.tm_hour, tm.tm_min);
+            },
+            Some('d') => {
+                let tm = local_time();
+                out += &format!("{} {} {:02}", DAYS[tm.tm_wday as usize % 7], MONTHS[tm.tm_mon as usize % 12], tm.tm_mday);
+            },
+            Some('$') => out += symbol,
+            Some('n') => out.push('\n'),
+            Some('e') => out.push('\x1b'),
+            Some('\\') => out.push('\\'),
+            // Bash marks non-printing text with these, the editor finds escape sequences itself
+            Some('[' | ']') => (),
+            Some('c') if chars.as_str().starts_with('{') => {
+                let rest = chars.as_str();
+                let Some(end) = rest.find('}') else {
+                    out += "\\c";
+                    continue;
+                };
+                let name = rest[1..end].to_owned();
+                chars = rest[end + 1..].chars();
+                if let Some((previous, start)) = coloured.take() {
+                    let text = out.split_off(start);
+                    out += &colour(&previous, text, status);
+                }
+                if !name.is_empty() {
+                    coloured = Some((name, out.len()));
+                }
+            },
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            },
+            None => out.push('\\'),
+        }
+    }
+    if let Some((name, start)) = coloured {
+        let text = out.split_off(start);
+        out += &colour(&name, text, status);
+    }
+    out
+}
@@ -0,0 +1,150 @@
+use std::ffi::CString;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectKind {
+    /// `<`
+    Read,
+    /// `>`
+    Write,
+    /// `>>`
+    Append,
+    /// `<>`
+    ReadWrite,
+    /// `>&` and `<&`, the target is a file descriptor
+    Dup,
+    /// `<<` and `<<-`, the target is the text of the here-document
+    HereDoc,
+    /// `<<<`, the target is the text, followed by a newline
+    HereString,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: i32,
+    pub kind: RedirectKind,
+    pub target: String,
+}
+
+impl Redirect {
+    fn open_flags(&self) -> i32 {
+        match self.kind {
+            RedirectKind::Read => libc::O_RDONLY,
+            RedirectKind::Write => libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+            RedirectKind::Append => libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND,
+            RedirectKind::ReadWrite => libc::O_RDWR | libc::O_CREAT,
+            RedirectKind::Dup | RedirectKind::HereDoc | RedirectKind::HereString => unreachable!(),
+        }
+    }
+
+    /// Put the text of a here-document in an anonymous file and return it, positioned at the start
+    fn here_doc(&self) -> io::Result<i32> {
+        let fd = unsafe { libc::memfd_create(c"here-document".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut text = self.target.clone();
+        if self.kind == RedirectKind::HereString {
+            text.push('\n');
+        }
+        let mut written = 0;
+        while written < text.len() {
+            let n = unsafe { libc::write(fd, text[written..].as_ptr().cast(), text.len() - written) };
+            if n == -1 {
+                let e = io::Error::last_os_error();
+                unsafe { libc::close(fd) };
+                return Err(e);
+            }
+            written += n as usize;
+        }
+        unsafe { libc::lseek(fd, 0, libc::SEEK_SET) };
+        Ok(fd)
+    }
+
+    /// Point self.fd at the target
+    fn apply(&self) -> io::Result<()> {
+        if self.kind == RedirectKind::Dup {
+            if self.target == "-" {
+                unsafe { libc::close(self.fd) };
+                return Ok(());
+            }
+            let target: i32 = self.target.parse()
+                .map_err(|_| io::Error::other(format!("{}: ambiguous redirect", self.target)))?;
+            if unsafe { libc::dup2(target, self.fd) } == -1 {
+                return Err(io::Error::other(format!("{target}: {}", io::Error::last_os_error())));
+            }
+            return Ok(());
+        }
+
+        let fd = if matches!(self.kind, RedirectKind::HereDoc | RedirectKind::HereString) {
+            self.here_doc()?
+        } else {
+            let path = CString::new(self.target.as_str())?;
+            let fd = unsafe { libc::open(path.as_ptr(), self.open_flags() | libc::O_CLOEXEC, 0o666) };
+            if fd == -1 {
+                return Err(io::Error::other(format!("{}: {}", self.target, io::Error::last_os_error())));
+            }
+            fd
+        };
+        if fd != self.fd {
+            // dup2 clears O_CLOEXEC on the new descriptor
+            let r = unsafe { libc::dup2(fd, self.fd) };
+            unsafe { libc::close(fd) };
+            if r == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        } else {
+            unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        }
+        Ok(())
+    }
+}
+
+/// Apply redirections in order, used in the child before exec
+pub fn apply(redirects: &[Redirect]) -> io::Result<()> {
+    for redirect in redirects {
+        redirect.apply()?;
+    }
+    Ok(())
+}
+
+/// File descriptors of the shell that were replaced by redirections.
+/// Used to run builtins with redirections without forking.
+pub struct SavedFds {
+    saved: Vec<(i32, Option<i32>)>,
+}
+
+impl SavedFds {
+    /// Save the affected fds and apply the redirections.
+    /// On failure the fds are restored before returning the error.
+    pub fn apply(redirects: &[Redirect]) -> io::Result<SavedFds> {
+        let mut saved = SavedFds { saved: vec![] };
+        // Make sure nothing buffered ends up in the redirected file
+        io::stdout().flush().ok();
+        for redirect in redirects {
+            if !saved.saved.iter().any(|(fd, _)| *fd == redirect.fd) {
+                let copy = unsafe { libc::fcntl(redirect.fd, libc::F_DUPFD_CLOEXEC, 10) };
+                saved.saved.push((redirect.fd, if copy == -1 { None } else { Some(copy) }));
+            }
+            if let Err(e) = redirect.apply() {
+                saved.restore();
+                return Err(e);
+            }
+        }
+        Ok(saved)
+    }
+
+    pub fn restore(self) {
+        io::stdout().flush().ok();
+        io::stderr().flush().ok();
+        for (fd, copy) in self.saved.into_iter().rev() {
+            match copy {
+                Some(copy) => unsafe {
+                    libc::dup2(copy, fd);
+                    libc::close(copy);
+                },
+                None => unsafe { libc::close(fd); },
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Shells and the loop reading their commands.
+//! The modules keep the state of the shell that is running in globals, each [Shell] keeps its own
+//! state while it waits and swaps it with the globals when it takes its turn.
+//! Between turns the globals hold the state of no shell, with the working directory of the process.
+
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use crate::{ast, dirs, editor, exec, hash, history, jobs, options, parser, prompt, trap, vars, ParseError, ALIASES};
+use crate::{exit_shell, read_line, run_line, run_source, run_string, run_traps, save_status};
+
+/// The prompt for the lines after the first of an unfinished command
+const DEFAULT_PS2: &str = "> ";
+
+/// Held by the shell whose state is in the globals
+static TURN: Mutex<()> = Mutex::new(());
+
+/// Where the commands come from
+pub enum Input {
+    /// A script file or a -c command string
+    Script(String),
+    /// Stdin that isn't a terminal, read a line at a time so
+    /// the commands can read the rest of it
+    Stdin,
+    /// A terminal, read with the line editor
+    Terminal,
+}
+
+/// What a shell keeps between commands
+struct State {
+    vars: vars::State,
+    aliases: HashMap<String, String>,
+    exec: exec::State,
+    options: Vec<&'static str>,
+    trap: trap::State,
+    jobs: jobs::State,
+    dirs: dirs::State,
+    hash: hash::Table,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            vars: vars::State::from_env(),
+            aliases: HashMap::new(),
+            exec: exec::State::default(),
+            options: vec![],
+            trap: trap::State::default(),
+            jobs: jobs::State::default(),
+            dirs: dirs::State::default(),
+            hash: hash::Table::default(),
+        }
+    }
+
+    /// Exchange the state with the one in the globals
+    fn swap(&mut self) {
+        vars::swap(&mut self.vars);
+        std::mem::swap(&mut *ALIASES.lock().unwrap(), &mut self.aliases);
+        exec::swap(&mut self.exec);
+        options::swap(&mut self.options);
+        trap::swap(&mut self.trap);
+        jobs::swap(&mut self.jobs);
+        dirs::swap(&mut self.dirs);
+        hash::swap(&mut self.hash);
+    }
+}
+
+/// The state of a shell swapped into the globals, it is swapped out again when dropped
+struct Turn<'a> {
+    state: RefMut<'a, State>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.state.swap();
+    }
+}
+
+/// Run something while no shell has its state in the globals
+pub(crate) fn between_turns<T>(f: impl FnOnce() -> T) -> T {
+    let _lock = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+    f()
+}
+
+/// A shell with its own variables, aliases, functions, options, traps, jobs and working directory.
+/// Shells of a process take turns, [Shell::eval] waits while another one runs.
+/// The working directory belongs to the process, a shell changes to its own for its turn and back after it.
+pub struct Shell {
+    state: RefCell<State>,
+    /// What `\$` shows in the prompt for users and for root
+    user_symbol: String,
+    root_symbol: String,
+}
+
+impl Default for Shell {
+    fn default() -> Shell {
+        Shell::new()
+    }
+}
+
+impl Shell {
+    /// A non-interactive shell for running commands from another program,
+    /// `exit` ends [Shell::eval] instead of the process
+    pub fn new() -> Shell {
+        Shell::create(exec::embed)
+    }
+
+    /// The shell of the schelp program, an interactive one gets job control
+    pub fn standalone(interactive: bool) -> Shell {
+        Shell::create(|| {
+            trap::init(interactive);
+            if interactive {
+                jobs::init();
+            }
+        })
+    }
+
+    /// A shell set up by init in its first turn
+    fn create(init: impl FnOnce()) -> Shell {
+        let shell = Shell {
+            state: RefCell::new(State::new()),
+            user_symbol: "$".to_owned(),
+            root_symbol: "#".to_owned(),
+        };
+        {
+            let _turn = shell.turn();
+            vars::set_arg0("schelp");
+            dirs::init();
+            init();
+        }
+        shell
+    }
+
+    /// Swap the state of the shell into the globals, waiting for the shell whose turn it is
+    fn turn(&self) -> Turn<'_> {
+        let lock = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut state = self.state.borrow_mut();
+        state.swap();
+        Turn { state, _lock: lock }
+    }
+
+    /// Set what `\$` shows in the prompt
+    pub fn set_prompt_symbols(&mut self, user: &str, root: &str) {
+        self.user_symbol = user.to_owned();
+        self.root_symbol = root.to_owned();
+    }
+
+    /// Run commands in the shell like a sourced file, returns the status of the last one
+    /// or the code given to `exit`
+    pub fn eval(&mut self, source: &str) -> i32 {
+        let _turn = self.turn();
+        let mut status = None;
+        exec::source(|| run_source(source, &mut status));
+        run_traps();
+        exec::take_exit().unwrap_or(status.unwrap_or(0))
+    }
+
+    /// Parse commands without running them, expanding the aliases of the shell
+    pub fn parse(&self, source: &str) -> Result<ast::List, ParseError> {
+        let _turn = self.turn();
+        parser::parse(source)
+    }
+
+    /// A variable or a parameter like `$1` or `$?`
+    pub fn var(&self, name: &str) -> Option<String> {
+        let _turn = self.turn();
+        vars::get(name)
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        let _turn = self.turn();
+        vars::set(name, value);
+    }
+
+    /// Export a variable to the commands run, setting it when a value is given
+    pub fn export(&mut self, name: &str, value: Option<&str>) {
+        let _turn = self.turn();
+        vars::export(name, value);
+    }
+
+    /// Set $0 and the positional parameters
+    pub fn set_args(&mut self, arg0: &str, args: Vec<String>) {
+        let _turn = self.turn();
+        vars::set_arg0(arg0);
+        vars::set_positional(args);
+    }
+
+    pub fn alias(&self, name: &str) -> Option<String> {
+        self.state.borrow().aliases.get(name).cloned()
+    }
+
+    pub fn set_alias(&mut self, name: &str, value: &str) {
+        self.state.borrow_mut().aliases.insert(name.to_owned(), value.to_owned());
+    }
+
+    /// The body of a function as it would be written in a script
+    pub fn function(&self, name: &str) -> Option<String> {
+        let _turn = self.turn();
+        exec::function(name).map(|body| body.to_string())
+    }
+
+    /// The ids and commands of the jobs that haven't completed or been disowned
+    pub fn jobs(&self) -> Vec<(usize, String)> {
+        let _turn = self.turn();
+        jobs::list()
+    }
+
+    /// Expand the prompt in the variable, or the default when it is not set
+    fn prompt(&self, var: &str, default: &str, status: Option<i32>) -> String {
+        let uid = unsafe { libc::getuid() };
+        let symbol = if uid == 0 { &self.root_symbol } else { &self.user_symbol };
+        let text = vars::get(var).unwrap_or(default.to_owned());
+        prompt::expand(&text, status, symbol)
+    }
+
+    /// Run the commands from the input until it ends or `exit` is run, then exit
+    pub fn run(&mut self, input: Input) -> ! {
+        let _turn = self.turn();
+        let interactive = matches!(input, Input::Terminal);
+        if interactive {
+            history::load();
+        }
+        let mut script = match &input {
+            Input::Script(text) => text.lines(),
+            _ => "".lines(),
+        };
+        let mut status: Option<i32> = None;
+        // Lines are collected until they form a complete command
+        let mut pending = String::new();
+        // ctrl-D presses ignored in a row
+        let mut ignored_eofs = 0;
+
+        loop {
+            let mut line: String = String::new();
+            let eof = match &input {
+                Input::Script(_) => match script.next() {
+                    Some(l) => {
+                        line = l.to_owned();
+                        false
+                    },
+                    None => true,
+                },
+                Input::Stdin => match read_line(&mut line) {
+                    Ok(n) => n == 0,
+                    Err(e) => {
+                        eprintln!("schelp: failed to read from stdin: {e}");
+                        true
+                    },
+                },
+                Input::Terminal => {
+                    jobs::notify();
+                    if pending.is_empty() && let Some(command) = vars::get("PROMPT_COMMAND") {
+                        run_string(&command);
+                        save_status(status);
+                    }
+                    // The lines after the first of an unfinished command get the secondary prompt
+                    let text = match pending.is_empty() {
+                        true => self.prompt("PS1", prompt::DEFAULT_PS1, status),
+                        false => self.prompt("PS2", DEFAULT_PS2, status),
+                    };
+                    // The editor only handles the last line of the prompt
+                    let (above, last) = match text.rsplit_once('\n') {
+                        Some((above, last)) => (Some(above), last),
+                        None => (None, text.as_str()),
+                    };
+                    if let Some(above) = above {
+                        println!("{above}");
+                    }
+                    let right = self.prompt("RPS1", "", status);
+                    match editor::read_line(last, &right) {
+                        Ok(Some(input)) => {
+                            ignored_eofs = 0;
+                            line = input;
+                            history::add(&line);
+                            false
+                        },
+                        // With ignoreeof ctrl-D only exits after IGNOREEOF times in a row
+                        Ok(None) if pending.is_empty() && options::enabled("ignoreeof") => {
+                            ignored_eofs += 1;
+                            let limit = vars::get("IGNOREEOF").and_then(|n| n.parse().ok()).unwrap_or(10);
+                            if ignored_eofs <= limit {
+                                eprintln!("Use \"exit\" to leave the shell.");
+                                continue;
+                            }
+                            true
+                        },
+                        Ok(None) => true,
+                        Err(e) => {
+                            if e.kind() != io::ErrorKind::Interrupted {
+                                eprintln!("schelp: failed to read from stdin: {e}");
+                                break;
+                            }
+                            // ctrl-C discards the line, and the lines before it
+                            println!();
+                            pending.clear();
+                            run_traps();
+                            status = Some(130);
+                            save_status(status);
+                            continue;
+                        },
+                    }
+                },
+            };
+            if eof {
+                // Reports the unfinished command
+                if !pending.is_empty() {
+                    run_line(&std::mem::take(&mut pending), &mut status);
+                    if interactive {
+                        continue;
+                    }
+                }
+                break;
+            }
+            pending.push_str(line.strip_suffix('\n').unwrap_or(&line));
+            pending.push('\n');
+            if parser::parse(&pending).is_err_and(|e| e.incomplete) {
+                continue;
+            }
+            let line = std::mem::take(&mut pending);
+            run_line(&line, &mut status);
+            run_traps();
+        }
+        exit_shell(status.unwrap_or(0));
+    }
+}
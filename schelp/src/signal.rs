@@ -1,48 +1,182 @@
-/// Don't completely trust these.
-#[repr(i32)]
-#[derive(Debug)]
-pub enum Signal {
-    SIGHUP = 1,
-    SIGINT,
-    SIGQUIT,
-    SIGILL,
-    SIGTRAP,
-    SIGABRT,
-    SIGBUS,
-    SIGFPE,
-    SIGKILL,
-    SIGUSR1,
-    SIGSEGV,
-    SIGUSR2,
-    SIGPIPE,
-    SIGALRM,
-    SIGTERM,
-    SIGSTKFLT,
-    SIGCHLD,
-    SIGCONT,
-    SIGSTOP,
-    SIGTSTP,
-    SIGTTIN,
-    SIGTTOU,
-    SIGURG,
-    SIGXCPU,
-    SIGXFSZ,
-    SIGVTALRM,
-    SIGPROF,
-    SIGWINCH,
-    SIGIO,
-    SIGPWR,
-    SIGSYS,
+/// Defines the standard signals with their numbers, taken from libc
+/// so they are correct for the architecture, and what they mean
+macro_rules! signals {
+    ($($(#[$attr:meta])* $name:ident, $description:literal;)*) => {
+        // Named like the libc constants, [Signal::name] relies on it
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum Signal {
+            $($(#[$attr])* $name,)*
+            /// A real-time signal, SIGRTMIN plus the offset
+            Realtime(i32),
+        }
+
+        const STANDARD: &[(Signal, i32, &str)] = &[
+            $($(#[$attr])* (Signal::$name, libc::$name, $description),)*
+        ];
+    };
+}
+
+signals! {
+    SIGHUP, "Hangup";
+    SIGINT, "Interrupt";
+    SIGQUIT, "Quit";
+    SIGILL, "Illegal instruction";
+    SIGTRAP, "Trace/breakpoint trap";
+    SIGABRT, "Aborted";
+    SIGBUS, "Bus error";
+    SIGFPE, "Floating point exception";
+    SIGKILL, "Killed";
+    SIGUSR1, "User defined signal 1";
+    SIGSEGV, "Segmentation fault";
+    SIGUSR2, "User defined signal 2";
+    SIGPIPE, "Broken pipe";
+    SIGALRM, "Alarm clock";
+    SIGTERM, "Terminated";
+    #[cfg(not(any(target_arch = "mips", target_arch = "mips64", target_arch = "sparc64")))]
+    SIGSTKFLT, "Stack fault";
+    SIGCHLD, "Child exited";
+    SIGCONT, "Continued";
+    SIGSTOP, "Stopped (signal)";
+    SIGTSTP, "Stopped";
+    SIGTTIN, "Stopped (tty input)";
+    SIGTTOU, "Stopped (tty output)";
+    SIGURG, "Urgent I/O condition";
+    SIGXCPU, "CPU time limit exceeded";
+    SIGXFSZ, "File size limit exceeded";
+    SIGVTALRM, "Virtual timer expired";
+    SIGPROF, "Profiling timer expired";
+    SIGWINCH, "Window changed";
+    SIGIO, "I/O possible";
+    SIGPWR, "Power failure";
+    SIGSYS, "Bad system call";
 }
 
 impl TryFrom<i32> for Signal {
     type Error = ();
 
     fn try_from(sig: i32) -> Result<Self, Self::Error> {
-        if sig >= Signal::SIGHUP as i32 && sig <= Signal::SIGSYS as i32 {
-            Ok(unsafe { std::mem::transmute(sig) })
-        } else {
-            Err(())
+        if let Some((signal, _, _)) = STANDARD.iter().find(|(_, number, _)| *number == sig) {
+            return Ok(*signal);
+        }
+        if (libc::SIGRTMIN()..=libc::SIGRTMAX()).contains(&sig) {
+            return Ok(Signal::Realtime(sig - libc::SIGRTMIN()));
+        }
+        Err(())
+    }
+}
+
+impl Signal {
+    /// All signals ordered by number
+    pub fn all() -> Vec<Signal> {
+        (1..=libc::SIGRTMAX()).filter_map(|sig| Signal::try_from(sig).ok()).collect()
+    }
+
+    /// Parse a signal name like `INT`, `SIGINT` or `RTMIN+1`, or a signal number
+    pub fn from_name(name: &str) -> Option<Signal> {
+        if let Ok(sig) = name.parse::<i32>() {
+            return Signal::try_from(sig).ok();
+        }
+        let name = name.to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        let realtime = match name {
+            "RTMIN" => Some(libc::SIGRTMIN()),
+            "RTMAX" => Some(libc::SIGRTMAX()),
+            _ => match (name.strip_prefix("RTMIN+"), name.strip_prefix("RTMAX-")) {
+                (Some(offset), _) => offset.parse::<i32>().ok().map(|n| libc::SIGRTMIN() + n),
+                (_, Some(offset)) => offset.parse::<i32>().ok().map(|n| libc::SIGRTMAX() - n),
+                _ => None,
+            },
+        };
+        if let Some(sig) = realtime {
+            return Signal::try_from(sig).ok().filter(|signal| matches!(signal, Signal::Realtime(_)));
+        }
+        STANDARD.iter().map(|(signal, _, _)| *signal).find(|signal| signal.name() == name)
+    }
+
+    pub fn number(&self) -> i32 {
+        match self {
+            Signal::Realtime(offset) => libc::SIGRTMIN() + offset,
+            signal => STANDARD.iter().find(|(s, _, _)| s == signal).map(|(_, number, _)| *number).unwrap(),
+        }
+    }
+
+    /// The name without the SIG prefix, real-time signals are counted from the nearest end
+    pub fn name(&self) -> String {
+        match self {
+            Signal::Realtime(0) => "RTMIN".to_owned(),
+            Signal::Realtime(offset) => {
+                let from_max = libc::SIGRTMAX() - libc::SIGRTMIN() - offset;
+                match from_max {
+                    0 => "RTMAX".to_owned(),
+                    n if n < *offset => format!("RTMAX-{n}"),
+                    _ => format!("RTMIN+{offset}"),
+                }
+            },
+            signal => format!("{:?}", signal)[3..].to_owned(),
         }
     }
+
+    /// What the signal means, like "Segmentation fault"
+    pub fn description(&self) -> String {
+        match self {
+            Signal::Realtime(offset) => format!("Real-time signal {offset}"),
+            signal => STANDARD.iter().find(|(s, _, _)| s == signal).map(|(_, _, d)| d.to_string()).unwrap(),
+        }
+    }
+}
+
+/// What a signal number means, with the number for unknown signals
+pub fn describe(sig: i32) -> String {
+    match Signal::try_from(sig) {
+        Ok(signal) => signal.description(),
+        Err(_) => format!("Unknown signal {sig}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(Signal::from_name("INT"), Some(Signal::SIGINT));
+        assert_eq!(Signal::from_name("sigterm"), Some(Signal::SIGTERM));
+        assert_eq!(Signal::from_name("9"), Some(Signal::SIGKILL));
+        assert_eq!(Signal::from_name("NOPE"), None);
+        assert_eq!(Signal::from_name("0"), None);
+        assert_eq!(Signal::SIGSEGV.name(), "SEGV");
+        assert_eq!(Signal::SIGTERM.number(), libc::SIGTERM);
+    }
+
+    #[test]
+    fn realtime_signals() {
+        let (min, max) = (libc::SIGRTMIN(), libc::SIGRTMAX());
+        assert_eq!(Signal::from_name("RTMIN").map(|s| s.number()), Some(min));
+        assert_eq!(Signal::from_name("SIGRTMIN+1").map(|s| s.number()), Some(min + 1));
+        assert_eq!(Signal::from_name("RTMAX-1").map(|s| s.number()), Some(max - 1));
+        assert_eq!(Signal::from_name("RTMIN+1000"), None);
+        // Named from the nearest end
+        assert_eq!(Signal::try_from(min + 1).unwrap().name(), "RTMIN+1");
+        assert_eq!(Signal::try_from(max - 1).unwrap().name(), "RTMAX-1");
+        assert_eq!(Signal::try_from(max).unwrap().name(), "RTMAX");
+        for signal in Signal::all() {
+            assert_eq!(Signal::from_name(&signal.name()), Some(signal));
+        }
+    }
+
+    #[test]
+    fn all_are_ordered_by_number() {
+        let numbers: Vec<i32> = Signal::all().iter().map(|s| s.number()).collect();
+        assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(numbers.first(), Some(&libc::SIGHUP));
+        assert_eq!(numbers.last(), Some(&libc::SIGRTMAX()));
+    }
+
+    #[test]
+    fn descriptions() {
+        assert_eq!(describe(libc::SIGSEGV), "Segmentation fault");
+        assert_eq!(describe(libc::SIGRTMIN() + 2), "Real-time signal 2");
+        assert_eq!(describe(1000), "Unknown signal 1000");
+    }
 }
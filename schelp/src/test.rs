@@ -0,0 +1,182 @@
+//! The test build_in, also available as `[ ... ]`.
+//! Up to four arguments are handled with the POSIX rules, longer expressions
+//! are parsed with `!`, `-a`, `-o` and parentheses.
+
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::ffi::CString;
+
+pub fn test(cmd: &str, args: &[String]) -> i32 {
+    let args = match cmd {
+        "[" => match args.split_last() {
+            Some((last, rest)) if last == "]" => rest,
+            _ => {
+                eprintln!("[: missing `]'");
+                return 2;
+            },
+        },
+        _ => args,
+    };
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    match evaluate(&args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{cmd}: {e}");
+            2
+        },
+    }
+}
+
+fn evaluate(args: &[&str]) -> Result<bool, String> {
+    match args {
+        [] => Ok(false),
+        [arg] => Ok(!arg.is_empty()),
+        ["!", arg] => Ok(arg.is_empty()),
+        [op, arg] => unary(op, arg),
+        [left, op, right] if is_binary(op) => binary(left, op, right),
+        ["!", ..] if args.len() <= 4 => evaluate(&args[1..]).map(|result| !result),
+        ["(", inner @ .., ")"] if args.len() <= 4 => evaluate(inner),
+        [_, _, _] => Err(format!("{}: binary operator expected", args[1])),
+        _ => {
+            let mut parser = Parser { args, pos: 0 };
+            let result = parser.or()?;
+            match parser.args.get(parser.pos) {
+                Some(arg) => Err(format!("{arg}: unexpected argument")),
+                None => Ok(result),
+            }
+        },
+    }
+}
+
+/// Parser for expressions with more than four arguments
+struct Parser<'a> {
+    args: &'a [&'a str],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<&'a str, String> {
+        let arg = self.args.get(self.pos).ok_or("argument expected")?;
+        self.pos += 1;
+        Ok(arg)
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut result = self.and()?;
+        while self.args.get(self.pos) == Some(&"-o") {
+            self.pos += 1;
+            result |= self.and()?;
+        }
+        Ok(result)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut result = self.not()?;
+        while self.args.get(self.pos) == Some(&"-a") {
+            self.pos += 1;
+            result &= self.not()?;
+        }
+        Ok(result)
+    }
+
+    fn not(&mut self) -> Result<bool, String> {
+        if self.args.get(self.pos) == Some(&"!") {
+            self.pos += 1;
+            return self.not().map(|result| !result);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let arg = self.next()?;
+        if arg == "(" {
+            let result = self.or()?;
+            return match self.next() {
+                Ok(")") => Ok(result),
+                _ => Err("missing `)'".to_owned()),
+            };
+        }
+        let rest = &self.args[self.pos..];
+        if let [op, right, ..] = rest && is_binary(op) {
+            self.pos += 2;
+            return binary(arg, op, right);
+        }
+        if is_unary(arg) && let [operand, ..] = rest {
+            self.pos += 1;
+            return unary(arg, operand);
+        }
+        Ok(!arg.is_empty())
+    }
+}
+
+fn is_unary(op: &str) -> bool {
+    matches!(op, "-b" | "-c" | "-d" | "-e" | "-f" | "-g" | "-h" | "-k" | "-L" | "-n" | "-p"
+        | "-r" | "-s" | "-S" | "-t" | "-u" | "-w" | "-x" | "-z")
+}
+
+fn is_binary(op: &str) -> bool {
+    matches!(op, "=" | "==" | "!=" | "<" | ">" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge"
+        | "-nt" | "-ot" | "-ef" | "-a" | "-o")
+}
+
+/// Whether the current user may access the file, mode is one of the libc::*_OK flags
+fn access(path: &str, mode: i32) -> bool {
+    let Ok(path) = CString::new(path) else { return false };
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
+
+fn unary(op: &str, arg: &str) -> Result<bool, String> {
+    let file = |f: fn(&Metadata) -> bool| fs::metadata(arg).is_ok_and(|m| f(&m));
+    Ok(match op {
+        "-n" => !arg.is_empty(),
+        "-z" => arg.is_empty(),
+        "-e" => fs::metadata(arg).is_ok(),
+        "-f" => file(|m| m.is_file()),
+        "-d" => file(|m| m.is_dir()),
+        "-b" => file(|m| m.file_type().is_block_device()),
+        "-c" => file(|m| m.file_type().is_char_device()),
+        "-p" => file(|m| m.file_type().is_fifo()),
+        "-S" => file(|m| m.file_type().is_socket()),
+        "-s" => file(|m| m.len() > 0),
+        "-g" => file(|m| m.permissions().mode() & 0o2000 != 0),
+        "-u" => file(|m| m.permissions().mode() & 0o4000 != 0),
+        "-k" => file(|m| m.permissions().mode() & 0o1000 != 0),
+        "-h" | "-L" => fs::symlink_metadata(arg).is_ok_and(|m| m.file_type().is_symlink()),
+        "-r" => access(arg, libc::R_OK),
+        "-w" => access(arg, libc::W_OK),
+        "-x" => access(arg, libc::X_OK),
+        "-t" => unsafe { libc::isatty(integer(arg)? as i32) == 1 },
+        _ => return Err(format!("{op}: unary operator expected")),
+    })
+}
+
+fn integer(arg: &str) -> Result<i64, String> {
+    arg.trim().parse().map_err(|_| format!("{arg}: integer expression expected"))
+}
+
+fn binary(left: &str, op: &str, right: &str) -> Result<bool, String> {
+    let modified = |path: &str| fs::metadata(path).ok().map(|m| (m.mtime(), m.mtime_nsec()));
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-eq" => integer(left)? == integer(right)?,
+        "-ne" => integer(left)? != integer(right)?,
+        "-lt" => integer(left)? < integer(right)?,
+        "-le" => integer(left)? <= integer(right)?,
+        "-gt" => integer(left)? > integer(right)?,
+        "-ge" => integer(left)? >= integer(right)?,
+        // A file that doesn't exist is older than any other
+        "-nt" => modified(left) > modified(right),
+        "-ot" => modified(left) < modified(right),
+        "-ef" => match (fs::metadata(left), fs::metadata(right)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        },
+        "-a" => !left.is_empty() && !right.is_empty(),
+        "-o" => !left.is_empty() || !right.is_empty(),
+        _ => return Err(format!("{op}: binary operator expected")),
+    })
+}
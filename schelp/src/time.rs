@@ -0,0 +1,136 @@
+//! The `time` keyword. The usage of the shell itself is taken from getrusage,
+//! that of the children from wait4 when they are reaped.
+//!
+//! TIMEFORMAT is printed with `%R`, `%U` and `%S` replaced by the real, user and system time,
+//! an optional precision and `l` like in `%3lR` give the digits after the point and minutes.
+//! `%P` is the CPU percentage, `%M` the max RSS in KiB, `%F` and `%f` the major and minor page faults.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::ast::TimeFormat;
+use crate::vars;
+
+/// Used when TIMEFORMAT is unset
+const DEFAULT_TIMEFORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS\nrss\t%MK\nfaults\t%F major, %f minor";
+
+const POSIX_TIMEFORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+#[derive(Default, Clone, Copy)]
+struct Usage {
+    user: Duration,
+    system: Duration,
+    /// In KiB
+    max_rss: i64,
+    major_faults: i64,
+    minor_faults: i64,
+}
+
+impl Usage {
+    fn add(&mut self, usage: &libc::rusage) {
+        self.user += duration(usage.ru_utime);
+        self.system += duration(usage.ru_stime);
+        self.max_rss = self.max_rss.max(usage.ru_maxrss);
+        self.major_faults += usage.ru_majflt;
+        self.minor_faults += usage.ru_minflt;
+    }
+}
+
+/// The usage of the children reaped by each `time` being run, the innermost last
+static TIMING: Mutex<Vec<Usage>> = Mutex::new(vec![]);
+
+fn duration(time: libc::timeval) -> Duration {
+    Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000)
+}
+
+fn own_usage() -> libc::rusage {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    usage
+}
+
+/// Count the resource usage of a child that terminated towards the commands being timed
+pub fn record(usage: &libc::rusage) {
+    for timing in TIMING.lock().unwrap().iter_mut() {
+        timing.add(usage);
+    }
+}
+
+/// Run a pipeline and report the time and resources it used on stderr
+pub fn time(format: TimeFormat, f: impl FnOnce() -> Option<i32>) -> Option<i32> {
+    TIMING.lock().unwrap().push(Usage::default());
+    let before = own_usage();
+    let start = Instant::now();
+
+    let status = f();
+
+    let real = start.elapsed();
+    let after = own_usage();
+    let mut usage = TIMING.lock().unwrap().pop().unwrap_or_default();
+    // Build_ins and functions run in the shell itself
+    usage.user += duration(after.ru_utime).saturating_sub(duration(before.ru_utime));
+    usage.system += duration(after.ru_stime).saturating_sub(duration(before.ru_stime));
+    usage.major_faults += after.ru_majflt - before.ru_majflt;
+    usage.minor_faults += after.ru_minflt - before.ru_minflt;
+
+    let format = match format {
+        TimeFormat::Posix => POSIX_TIMEFORMAT.to_owned(),
+        TimeFormat::Default => vars::get("TIMEFORMAT").unwrap_or(DEFAULT_TIMEFORMAT.to_owned()),
+    };
+    // An empty TIMEFORMAT turns the report off
+    if !format.is_empty() {
+        eprintln!("{}", report(&format, real, &usage));
+    }
+    status
+}
+
+/// Seconds with the given digits after the point, as minutes and seconds like `1m2.345s` if long
+fn seconds(time: Duration, precision: usize, long: bool) -> String {
+    let seconds = time.as_secs_f64();
+    // Truncated rather than rounded, like bash
+    let scale = 10f64.powi(precision as i32);
+    let seconds = (seconds * scale).trunc() / scale;
+    if long {
+        let minutes = (seconds / 60.0).trunc();
+        format!("{minutes}m{:.precision$}s", seconds - minutes * 60.0)
+    } else {
+        format!("{seconds:.precision$}")
+    }
+}
+
+fn report(format: &str, real: Duration, usage: &Usage) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut precision = 3;
+        if let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            precision = digit.min(6) as usize;
+            chars.next();
+        }
+        let long = chars.next_if_eq(&'l').is_some();
+        match chars.next() {
+            Some('R') => out += &seconds(real, precision, long),
+            Some('U') => out += &seconds(usage.user, precision, long),
+            Some('S') => out += &seconds(usage.system, precision, long),
+            Some('P') => {
+                let cpu = (usage.user + usage.system).as_secs_f64();
+                let real = real.as_secs_f64();
+                let percent = if real > 0.0 { cpu / real * 100.0 } else { 0.0 };
+                out += &format!("{percent:.2}");
+            },
+            Some('M') => out += &usage.max_rss.to_string(),
+            Some('F') => out += &usage.major_faults.to_string(),
+            Some('f') => out += &usage.minor_faults.to_string(),
+            Some('%') => out.push('%'),
+            Some(c) => {
+                out.push('%');
+                out.push(c);
+            },
+            None => out.push('%'),
+        }
+    }
+    out
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use crate::signal::Signal;
+
+/// Commands set with the trap build_in, 0 is EXIT.
+/// An empty command means the signal is ignored.
+static TRAPS: LazyLock<Mutex<HashMap<i32, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Bitmask of signals that were caught but not handled yet, bit 0 is signal 1
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Signals an interactive shell ignores, SIGINT is caught instead so it can discard the line
+const INTERACTIVE_IGNORED: [i32; 4] = [libc::SIGQUIT, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU];
+
+/// Signals a child gets the default disposition for
+const RESET_IN_CHILD: [i32; 6] = [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU, libc::SIGPIPE];
+
+extern "C" fn handler(sig: i32) {
+    PENDING.fetch_or(bit(sig), Ordering::SeqCst);
+}
+
+fn bit(sig: i32) -> u64 {
+    1 << (sig - 1)
+}
+
+fn catch(sig: i32) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as *const () as usize;
+        // No SA_RESTART, a read from the terminal should be interrupted
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(sig, &action, std::ptr::null_mut());
+    }
+}
+
+/// Install the disposition a signal should have in the shell itself
+fn install(sig: i32) {
+    let trap = TRAPS.lock().unwrap().get(&sig).cloned();
+    let interactive = INTERACTIVE.load(Ordering::Relaxed);
+    match trap {
+        Some(cmd) if cmd.is_empty() => unsafe { libc::signal(sig, libc::SIG_IGN); },
+        Some(_) => catch(sig),
+        None if interactive && sig == libc::SIGINT => catch(sig),
+        None if interactive && INTERACTIVE_IGNORED.contains(&sig) => unsafe { libc::signal(sig, libc::SIG_IGN); },
+        None => unsafe { libc::signal(sig, libc::SIG_DFL); },
+    }
+}
+
+/// The traps of a shell and whether it is interactive while it isn't its turn, see [swap]
+#[derive(Default)]
+pub struct State {
+    traps: HashMap<i32, String>,
+    interactive: bool,
+}
+
+/// Exchange the traps with those of another shell, the signals either of them handles are installed again
+pub fn swap(state: &mut State) {
+    let mut traps = TRAPS.lock().unwrap();
+    let mut sigs: Vec<i32> = traps.keys().chain(state.traps.keys()).copied().filter(|sig| *sig != 0).collect();
+    if interactive() || state.interactive {
+        sigs.push(libc::SIGINT);
+        sigs.extend(INTERACTIVE_IGNORED);
+    }
+    std::mem::swap(&mut *traps, &mut state.traps);
+    drop(traps);
+    state.interactive = INTERACTIVE.swap(state.interactive, Ordering::Relaxed);
+    for sig in sigs {
+        install(sig);
+    }
+}
+
+pub fn init(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+    if interactive {
+        install(libc::SIGINT);
+        for sig in INTERACTIVE_IGNORED {
+            install(sig);
+        }
+    }
+}
+
+/// Restore default dispositions in a forked child and forget the traps, EXIT included.
+/// Signals ignored with trap stay ignored.
+pub fn reset_child() {
+    let mut traps = TRAPS.lock().unwrap();
+    for sig in RESET_IN_CHILD.iter().copied().chain(traps.keys().copied().filter(|sig| *sig != 0)) {
+        if traps.get(&sig).is_some_and(|cmd| cmd.is_empty()) {
+            continue;
+        }
+        unsafe { libc::signal(sig, libc::SIG_DFL) };
+    }
+    traps.retain(|_, cmd| cmd.is_empty());
+}
+
+/// Act as if SIGINT was caught, used when the foreground job was interrupted
+pub fn interrupt() {
+    PENDING.fetch_or(bit(libc::SIGINT), Ordering::SeqCst);
+}
+
+/// Whether the signal was caught, without clearing it
+pub fn pending(sig: i32) -> bool {
+    PENDING.load(Ordering::SeqCst) & bit(sig) != 0
+}
+
+/// Clear all caught signals and return the trap commands that should run
+pub fn take_pending() -> Vec<String> {
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    let traps = TRAPS.lock().unwrap();
+    (1..=libc::SIGRTMAX())
+        .filter(|sig| pending & bit(*sig) != 0)
+        .filter_map(|sig| traps.get(&sig).cloned())
+        .filter(|cmd| !cmd.is_empty())
+        .collect()
+}
+
+/// Whether the shell reads commands from a terminal
+pub fn interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// The command to run when the shell exits
+pub fn exit_trap() -> Option<String> {
+    TRAPS.lock().unwrap().remove(&0).filter(|cmd| !cmd.is_empty())
+}
+
+fn parse_signal(name: &str) -> Option<i32> {
+    match name {
+        "0" | "EXIT" => Some(0),
+        _ => Signal::from_name(name).map(|sig| sig.number()),
+    }
+}
+
+fn signal_name(sig: i32) -> String {
+    match Signal::try_from(sig) {
+        Ok(signal) => signal.name(),
+        Err(_) if sig == 0 => "EXIT".to_owned(),
+        Err(_) => sig.to_string(),
+    }
+}
+
+/// The trap build_in
+pub fn trap(args: &[String]) -> i32 {
+    if args.is_empty() {
+        let traps = TRAPS.lock().unwrap();
+        let mut sigs: Vec<&i32> = traps.keys().collect();
+        sigs.sort();
+        for sig in sigs {
+            println!("trap -- '{}' {}", traps[sig], signal_name(*sig));
+        }
+        return 0;
+    }
+    if args[0] == "-l" {
+        for sig in Signal::all() {
+            println!("{:>2}) SIG{}", sig.number(), sig.name());
+        }
+        return 0;
+    }
+
+    // trap SIG... resets the signals like trap - SIG...
+    let (action, sigs) = match parse_signal(&args[0]) {
+        Some(_) if args.len() == 1 => ("-", args),
+        _ => (args[0].as_str(), &args[1..]),
+    };
+
+    let mut code = 0;
+    for name in sigs {
+        let Some(sig) = parse_signal(name) else {
+            eprintln!("trap: {name}: invalid signal specification");
+            code = 1;
+            continue;
+        };
+        if sig == libc::SIGKILL || sig == libc::SIGSTOP {
+            eprintln!("trap: {name}: cannot be trapped");
+            code = 1;
+            continue;
+        }
+        match action {
+            "-" => TRAPS.lock().unwrap().remove(&sig),
+            _ => TRAPS.lock().unwrap().insert(sig, action.to_owned()),
+        };
+        if sig != 0 {
+            install(sig);
+        }
+    }
+    code
+}
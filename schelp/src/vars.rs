@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{LazyLock, Mutex};
+use crate::lexer::is_name;
+use crate::options;
+
+#[derive(Clone)]
+struct Var {
+    value: String,
+    /// Exported variables are passed to child processes
+    exported: bool,
+}
+
+/// Shell variables of the shell whose turn it is, see [swap]
+static VARIABLES: LazyLock<Mutex<HashMap<String, Var>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The variables made local in a function, with the values they shadow
+type Scope = Vec<(String, Option<Var>)>;
+
+/// Scopes of the functions being run
+static SCOPES: Mutex<Vec<Scope>> = Mutex::new(vec![]);
+
+/// `$0`, the name of the script or the shell
+static ARG0: Mutex<String> = Mutex::new(String::new());
+
+/// `$1` and up, the arguments of the script or the function being run
+static POSITIONAL: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// `$$`, subshells keep the pid of the shell they were forked from
+static SHELL_PID: LazyLock<i32> = LazyLock::new(|| unsafe { libc::getpid() });
+
+/// `$!`, the pid of the last background job, 0 if there is none
+static LAST_BACKGROUND: AtomicI32 = AtomicI32::new(0);
+
+/// The variables and parameters of a shell while it isn't running
+pub struct State {
+    variables: HashMap<String, Var>,
+    arg0: String,
+    positional: Vec<String>,
+    last_background: i32,
+    scopes: Vec<Scope>,
+}
+
+impl State {
+    /// Variables initialized with the environment of the process, all exported
+    pub fn from_env() -> State {
+        State {
+            variables: std::env::vars().map(|(name, value)| (name, Var { value, exported: true })).collect(),
+            arg0: String::new(),
+            positional: vec![],
+            last_background: 0,
+            scopes: vec![],
+        }
+    }
+}
+
+/// Exchange the variables in use with those of another shell
+pub fn swap(state: &mut State) {
+    std::mem::swap(&mut *VARIABLES.lock().unwrap(), &mut state.variables);
+    std::mem::swap(&mut *ARG0.lock().unwrap(), &mut state.arg0);
+    std::mem::swap(&mut *POSITIONAL.lock().unwrap(), &mut state.positional);
+    state.last_background = LAST_BACKGROUND.swap(state.last_background, Ordering::Relaxed);
+    std::mem::swap(&mut *SCOPES.lock().unwrap(), &mut state.scopes);
+}
+
+pub fn get(name: &str) -> Option<String> {
+    // Positional and special parameters
+    if let Ok(n) = name.parse::<usize>() {
+        return match n {
+            0 => Some(ARG0.lock().unwrap().clone()),
+            n => POSITIONAL.lock().unwrap().get(n - 1).cloned(),
+        };
+    }
+    match name {
+        "#" => return Some(POSITIONAL.lock().unwrap().len().to_string()),
+        "@" | "*" => return Some(POSITIONAL.lock().unwrap().join(" ")),
+        "?" => return Some(get("status").unwrap_or("0".to_owned())),
+        "$" => return Some(SHELL_PID.to_string()),
+        "!" => {
+            let pid = LAST_BACKGROUND.load(Ordering::Relaxed);
+            return (pid != 0).then(|| pid.to_string());
+        },
+        "-" => return Some(options::flags()),
+        _ => (),
+    }
+    VARIABLES.lock().unwrap().get(name).map(|var| var.value.clone())
+}
+
+/// Set a variable, new variables are local to the shell
+pub fn set(name: &str, value: &str) {
+    let mut vars = VARIABLES.lock().unwrap();
+    match vars.get_mut(name) {
+        Some(var) => var.value = value.to_owned(),
+        None => { vars.insert(name.to_owned(), Var { value: value.to_owned(), exported: false }); },
+    }
+}
+
+/// Mark a variable as exported, optionally setting it as well
+pub fn export(name: &str, value: Option<&str>) {
+    let mut vars = VARIABLES.lock().unwrap();
+    match (vars.get_mut(name), value) {
+        (Some(var), value) => {
+            var.exported = true;
+            if let Some(value) = value {
+                var.value = value.to_owned();
+            }
+        },
+        (None, value) => {
+            vars.insert(name.to_owned(), Var { value: value.unwrap_or_default().to_owned(), exported: true });
+        },
+    }
+}
+
+/// A variable as it was before a prefix assignment, put back by [restore]
+pub struct Saved {
+    name: String,
+    var: Option<Var>,
+}
+
+/// Set and export a prefix assignment like `VAR=value cmd` for a build_in or function
+pub fn assign_exported(name: &str, value: &str) -> Saved {
+    let var = VARIABLES.lock().unwrap().get(name).cloned();
+    export(name, Some(value));
+    Saved { name: name.to_owned(), var }
+}
+
+pub fn restore(saved: Saved) {
+    let mut vars = VARIABLES.lock().unwrap();
+    match saved.var {
+        Some(var) => { vars.insert(saved.name, var); },
+        None => { vars.remove(&saved.name); },
+    }
+}
+
+pub fn unset(name: &str) {
+    VARIABLES.lock().unwrap().remove(name);
+}
+
+/// All exported variables, sorted by name
+pub fn exported() -> Vec<(String, String)> {
+    let mut exported: Vec<(String, String)> = VARIABLES.lock().unwrap().iter()
+        .filter(|(_, var)| var.exported)
+        .map(|(name, var)| (name.clone(), var.value.clone()))
+        .collect();
+    exported.sort();
+    exported
+}
+
+/// Build the environment for a child, `overrides` are prefix assignments like `VAR=value cmd`
+pub fn environ(overrides: &[(String, String)]) -> Vec<CString> {
+    let mut env = exported();
+    for (name, value) in overrides {
+        match env.iter_mut().find(|(n, _)| n == name) {
+            Some(var) => var.1 = value.clone(),
+            None => env.push((name.clone(), value.clone())),
+        }
+    }
+    env.iter()
+        .filter_map(|(name, value)| CString::new(format!("{name}={value}")).ok())
+        .collect()
+}
+
+/// Split a `NAME=value` word into its name and value
+pub fn split_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+    is_name(name).then_some((name, value))
+}
+
+/// Names of all variables, sorted
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = VARIABLES.lock().unwrap().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+pub fn set_arg0(name: &str) {
+    *ARG0.lock().unwrap() = name.to_owned();
+    LazyLock::force(&SHELL_PID);
+}
+
+pub fn set_last_background(pid: i32) {
+    LAST_BACKGROUND.store(pid, Ordering::Relaxed);
+}
+
+pub fn positional() -> Vec<String> {
+    POSITIONAL.lock().unwrap().clone()
+}
+
+/// Replace the positional parameters, returns the previous ones
+pub fn set_positional(params: Vec<String>) -> Vec<String> {
+    std::mem::replace(&mut POSITIONAL.lock().unwrap(), params)
+}
+
+/// Drop the first n positional parameters, false if there are not enough
+pub fn shift(n: usize) -> bool {
+    let mut params = POSITIONAL.lock().unwrap();
+    if n > params.len() {
+        return false;
+    }
+    params.drain(..n);
+    true
+}
+
+/// Start a scope for the local variables of a function
+pub fn push_scope() {
+    SCOPES.lock().unwrap().push(vec![]);
+}
+
+/// Restore the variables that were shadowed by locals of the function that returned
+pub fn pop_scope() {
+    let Some(scope) = SCOPES.lock().unwrap().pop() else { return };
+    let mut vars = VARIABLES.lock().unwrap();
+    for (name, previous) in scope.into_iter().rev() {
+        match previous {
+            Some(var) => { vars.insert(name, var); },
+            None => { vars.remove(&name); },
+        }
+    }
+}
+
+/// Make a variable local to the current function
+pub fn local(name: &str, value: Option<&str>) -> Result<(), String> {
+    let mut scopes = SCOPES.lock().unwrap();
+    let scope = scopes.last_mut().ok_or("can only be used in a function")?;
+    let mut vars = VARIABLES.lock().unwrap();
+    if !scope.iter().any(|(n, _)| n == name) {
+        scope.push((name.to_owned(), vars.get(name).cloned()));
+        // A new local starts out empty and is not exported
+        vars.insert(name.to_owned(), Var { value: String::new(), exported: false });
+    }
+    if let Some(value) = value {
+        vars.get_mut(name).unwrap().value = value.to_owned();
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+//! The working directory of a process running shells. Only one test, no other shell
+//! may take its turn and change the directory while it is checked.
+
+use schelp::Shell;
+
+#[test]
+fn shells_change_back_to_the_directory_of_the_process() {
+    let before = std::env::current_dir().unwrap();
+    let mut shell = Shell::new();
+    assert_eq!(shell.eval("cd /"), 0);
+    assert_eq!(std::env::current_dir().unwrap(), before);
+    // The shell is still in its own directory on its next turn
+    assert_eq!(shell.eval("cd tmp"), 0);
+    assert_eq!(shell.var("PWD").as_deref(), Some("/tmp"));
+    assert_eq!(std::env::current_dir().unwrap(), before);
+}
@@ -0,0 +1,72 @@
+//! Runs each script in tests/golden and compares what it printed and its exit status
+//! with NAME.stdout, NAME.stderr and NAME.status next to it. Missing stderr and status
+//! files mean nothing on stderr and a status of 0. `UPDATE_GOLDEN=1` rewrites them.
+//! The scripts can run `schelp` but no other commands.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The expected contents of a file next to the script, empty when it doesn't exist
+fn expected(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+/// Write the file, or remove it when its contents are the default
+fn update(path: &Path, contents: &str, default: &str) {
+    if contents == default {
+        fs::remove_file(path).ok();
+    } else {
+        fs::write(path, contents).unwrap();
+    }
+}
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let updating = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut scripts: Vec<_> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sh"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty(), "no scripts in {}", dir.display());
+
+    // Scripts only get the shell itself, not the host's tools
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_schelp")).parent().unwrap();
+    let mut failed = vec![];
+    for script in scripts {
+        let output = Command::new(env!("CARGO_BIN_EXE_schelp"))
+            // The host's /etc/schelprc would change the output
+            .arg("--norc")
+            .arg(script.file_name().unwrap())
+            .current_dir(&dir)
+            .env_clear()
+            .env("PATH", bin_dir)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let status = format!("{}\n", output.status.code().unwrap_or(-1));
+
+        let (stdout_file, stderr_file, status_file) =
+            (script.with_extension("stdout"), script.with_extension("stderr"), script.with_extension("status"));
+        if updating {
+            fs::write(&stdout_file, stdout.as_ref()).unwrap();
+            update(&stderr_file, &stderr, "");
+            update(&status_file, &status, "0\n");
+            continue;
+        }
+        let expected_status = match expected(&status_file) {
+            status if status.is_empty() => "0\n".to_owned(),
+            status => status,
+        };
+        if stdout != expected(&stdout_file) || stderr != expected(&stderr_file) || status != expected_status {
+            failed.push(format!(
+                "{}\n--- stdout\n{stdout}--- stderr\n{stderr}--- status {status}",
+                script.display(),
+            ));
+        }
+    }
+    assert!(failed.is_empty(), "scripts with unexpected output:\n{}", failed.join("\n"));
+}
@@ -0,0 +1,10 @@
+# Prefix assignments are exported to build_ins and functions while they run
+child() { schelp --norc -c 'echo "child sees [$FOO]"'; }
+FOO=bar child
+show() { child; echo "in show $FOO"; }
+FOO=baz show
+echo "after [$FOO]"
+FOO=keep
+FOO=tmp show
+echo "after $FOO"
+child
@@ -0,0 +1,4 @@
+# Scripts run background jobs without announcing them
+{ echo in background; } &
+wait
+echo "waited $?"
@@ -0,0 +1,29 @@
+# Loops, conditions, case and functions
+greet() {
+    echo "hello $1"
+    return 3
+}
+greet world
+echo "status $?"
+
+for i in 1 2 3; do
+    if [ $i = 2 ]; then
+        continue
+    fi
+    echo "i=$i"
+done
+
+n=0
+while [ $n -lt 5 ]; do
+    n=$((n + 1))
+    [ $n = 4 ] && break
+done
+echo "n=$n"
+
+case foo.txt in
+    *.rs) echo rust ;;
+    *.txt) echo text ;;
+esac
+
+true && echo and || echo or
+false || echo or
@@ -0,0 +1,10 @@
+# pushd takes -L, -P and -- like cd, then a directory or +N and -N
+cd /
+pushd -L /tmp
+pushd -P -- /
+pushd -1
+pushd +1
+popd
+pushd -X
+echo "status $?"
+dirs -v
@@ -0,0 +1,7 @@
+# set -e stops at the first failure outside of a condition
+set -e
+if false; then echo no; fi
+false || echo handled
+echo before
+(exit 7)
+echo after
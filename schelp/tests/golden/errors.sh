@@ -0,0 +1,7 @@
+# Errors go to stderr and the script carries on
+no_such_command_here
+echo "status $?"
+cd /no/such/dir
+echo "status $?"
+exit 5
+echo unreachable
@@ -0,0 +1,10 @@
+# Parameters, arithmetic, command substitution and quoting
+name=schelp
+echo "${name} ${#name} ${name%lp} ${name#sch}"
+echo "${unset_var:-default} ${name:+set}"
+echo $((2 * (3 + 4))) $((7 % 3))
+echo "$(echo inner) `echo back`"
+echo 'single $name' "double $name" \$escaped
+set -- a "b c" d
+echo "$#" "$1" "$2"
+for arg in "$@"; do echo "[$arg]"; done
@@ -0,0 +1,9 @@
+# Pipes, here documents and redirections
+echo one two three | { read a b c; echo "$c $b $a"; }
+while read line; do
+    echo "read $line"
+done <<END
+here $((1 + 1))
+END
+echo to-stderr >&2
+{ echo grouped; echo out; } | while read line; do echo "got $line"; done
@@ -0,0 +1,10 @@
+# Signal names and numbers, and statuses of commands killed by a signal
+kill -l 15 TERM 143
+kill -l NOPE
+echo "status $?"
+trap 'echo caught USR1' USR1
+kill -USR1 $$
+schelp --norc -c 'kill -KILL $$'
+echo "killed $?"
+schelp --norc -c 'kill -s TERM $$'
+echo "terminated $?"
@@ -0,0 +1,5 @@
+# -n only checks the syntax, of the -c string when there is one
+schelp --norc -n -c 'if true; then'
+echo "status $?"
+schelp --norc -n -c 'echo not run' name
+echo "status $?"
@@ -0,0 +1,8 @@
+# Subshells forget the traps of the shell, the EXIT trap runs once
+trap "echo bye" EXIT
+(exit 2)
+echo "subshell $?"
+(trap "echo sub bye" EXIT; exit 3)
+echo "own trap $?"
+echo piped | { read line; exit 4; }
+echo "pipeline $?"
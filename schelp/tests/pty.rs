@@ -0,0 +1,97 @@
+//! Interactive shells on a pseudo terminal, where job control is on
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+struct Pty {
+    master: File,
+    child: Child,
+    /// Everything read from the terminal so far
+    output: String,
+}
+
+impl Pty {
+    /// Start an interactive shell with the pty as its controlling terminal
+    fn spawn() -> Pty {
+        let (mut master, mut slave) = (0, 0);
+        let size = libc::winsize { ws_row: 24, ws_col: 200, ws_xpixel: 0, ws_ypixel: 0 };
+        let r = unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &size) };
+        assert_eq!(r, 0, "openpty failed");
+        let slave = unsafe { OwnedFd::from_raw_fd(slave) };
+        let home = std::env::temp_dir().join(format!("schelp-pty-{}", std::process::id()));
+        std::fs::create_dir_all(&home).unwrap();
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_schelp"));
+        command.arg("--norc")
+            .env("PATH", "/bin:/usr/bin")
+            .env("HOME", &home)
+            .env("PS1", "$ ")
+            .stdin(Stdio::from(slave.try_clone().unwrap()))
+            .stdout(Stdio::from(slave.try_clone().unwrap()))
+            .stderr(Stdio::from(slave));
+        unsafe {
+            command.pre_exec(|| {
+                libc::setsid();
+                libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0);
+                Ok(())
+            });
+        }
+        let child = command.spawn().unwrap();
+        Pty { master: unsafe { File::from_raw_fd(master) }, child, output: String::new() }
+    }
+
+    fn send(&mut self, line: &str) {
+        self.master.write_all(format!("{line}\r").as_bytes()).unwrap();
+    }
+
+    /// Read until the output contains the text, panics after a few seconds
+    fn expect(&mut self, text: &str) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !self.output.contains(text) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            assert!(!left.is_zero(), "timed out waiting for {text:?} in {:?}", self.output);
+            let mut poll = libc::pollfd { fd: std::os::fd::AsRawFd::as_raw_fd(&self.master), events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut poll, 1, left.as_millis() as i32) } <= 0 {
+                continue;
+            }
+            let mut buf = [0; 4096];
+            match self.master.read(&mut buf) {
+                Ok(n) if n > 0 => self.output.push_str(&String::from_utf8_lossy(&buf[..n])),
+                _ => panic!("the shell exited, output: {:?}", self.output),
+            }
+        }
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+#[test]
+fn build_ins_in_pipelines_run_their_commands() {
+    let mut pty = Pty::spawn();
+    // Expansions keep the results from matching the echoed input
+    pty.send("env /bin/echo from-$((1))-env | cat; echo env-status=$?");
+    pty.expect("from-1-env");
+    pty.expect("env-status=0");
+    pty.send("command /bin/echo from-$((2))-command | cat; echo command-status=$?");
+    pty.expect("from-2-command");
+    pty.expect("command-status=0");
+    assert!(!pty.output.contains("Stopped"), "{:?}", pty.output);
+    pty.send("exit");
+}
+
+#[test]
+fn nul_bytes_are_not_inserted() {
+    let mut pty = Pty::spawn();
+    pty.send("echo nul-$((1))\0-ignored");
+    pty.expect("nul-1-ignored");
+    pty.send("exit");
+}
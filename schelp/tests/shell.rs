@@ -0,0 +1,130 @@
+//! The library API. Each shell keeps its own state, so the tests don't share any.
+
+use std::path::Path;
+use schelp::Shell;
+
+/// A shell that finds no commands but schelp itself
+fn shell() -> Shell {
+    let mut shell = Shell::new();
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_schelp")).parent().unwrap();
+    shell.set_var("PATH", &bin_dir.to_string_lossy());
+    shell
+}
+
+#[test]
+fn eval_returns_the_status() {
+    let mut shell = shell();
+    assert_eq!(shell.eval("true"), 0);
+    assert_eq!(shell.eval("false"), 1);
+    assert_eq!(shell.eval("x=1\ny=$((x + 1))"), 0);
+    assert_eq!(shell.var("y").as_deref(), Some("2"));
+    assert_eq!(shell.var("?").as_deref(), Some("0"));
+}
+
+#[test]
+fn exit_ends_eval() {
+    let mut shell = shell();
+    shell.set_var("x", "1");
+    assert_eq!(shell.eval("exit 3\nx=unreachable"), 3);
+    assert_eq!(shell.var("x").as_deref(), Some("1"));
+    assert_eq!(shell.eval("for i in 1 2; do exit 4; done"), 4);
+    assert_eq!(shell.eval("true"), 0);
+}
+
+#[test]
+fn shells_keep_their_own_variables() {
+    let mut first = shell();
+    let mut second = shell();
+    first.set_var("name", "first");
+    second.eval("name=second");
+    assert_eq!(first.var("name").as_deref(), Some("first"));
+    assert_eq!(second.var("name").as_deref(), Some("second"));
+
+    first.export("exported", Some("yes"));
+    let child = "[ \"$(schelp --norc -c 'echo $exported')\" = yes ]";
+    assert_eq!(first.eval(child), 0);
+    assert_eq!(second.eval(child), 1);
+    assert_eq!(second.var("exported"), None);
+}
+
+#[test]
+fn shells_keep_their_own_functions_and_aliases() {
+    let mut first = shell();
+    let mut second = shell();
+    first.set_var("name", "world");
+    first.eval("greet() {\n    greeting=\"hello $name\"\n}\ngreet");
+    assert_eq!(first.var("greeting").as_deref(), Some("hello world"));
+    assert!(first.function("greet").is_some());
+    assert_eq!(second.function("greet"), None);
+    assert_eq!(second.eval("greet"), 127);
+
+    first.set_alias("ll", "ls -l");
+    assert_eq!(first.alias("ll").as_deref(), Some("ls -l"));
+    assert_eq!(second.alias("ll"), None);
+    first.set_alias("ok", "true");
+    assert_eq!(first.eval("ok"), 0);
+    assert_eq!(second.eval("ok"), 127);
+}
+
+#[test]
+fn set_args_sets_the_positional_parameters() {
+    let mut shell = shell();
+    assert_eq!(shell.var("0").as_deref(), Some("schelp"));
+    shell.set_args("script", vec!["a".to_owned(), "b c".to_owned()]);
+    assert_eq!(shell.var("0").as_deref(), Some("script"));
+    assert_eq!(shell.var("#").as_deref(), Some("2"));
+    shell.eval("second=$2");
+    assert_eq!(shell.var("second").as_deref(), Some("b c"));
+}
+
+#[test]
+fn shells_keep_their_own_directory() {
+    let root = std::env::temp_dir().join(format!("schelp-shell-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("first/sub/only-first")).unwrap();
+    std::fs::create_dir_all(root.join("second/sub")).unwrap();
+    let mut first = shell();
+    let mut second = shell();
+    first.set_var("root", &root.to_string_lossy());
+    second.set_var("root", &root.to_string_lossy());
+    assert_eq!(first.eval("cd \"$root/first\""), 0);
+    assert_eq!(second.eval("cd \"$root/second\""), 0);
+    // Relative paths are looked up in the directory of the shell that runs them
+    assert_eq!(first.eval("cd sub && [ -d only-first ]"), 0);
+    assert_eq!(second.eval("cd sub && [ -d only-first ]"), 1);
+    assert_eq!(first.var("PWD"), Some(format!("{}/first/sub", root.display())));
+    assert_eq!(second.var("PWD"), Some(format!("{}/second/sub", root.display())));
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn parse_reports_incomplete_commands() {
+    assert!(schelp::parse("if true; then echo yes; fi").is_ok());
+    let e = schelp::parse("if true; then").unwrap_err();
+    assert!(e.incomplete);
+    assert!(!schelp::parse("echo )").unwrap_err().incomplete);
+}
+
+#[test]
+fn parse_expands_the_aliases_of_the_shell() {
+    let mut shell = shell();
+    shell.set_alias("block", "if true; then");
+    assert!(shell.parse("block echo yes; fi").is_ok());
+    // Without a shell no aliases are expanded
+    assert!(!schelp::parse("block echo yes; fi").unwrap_err().incomplete);
+}
+
+#[test]
+fn shells_only_wait_for_their_own_children() {
+    let mut first = shell();
+    let mut second = shell();
+    let mut host_child = std::process::Command::new(env!("CARGO_BIN_EXE_schelp"))
+        .args(["--norc", "-c", "exit 5"])
+        .spawn()
+        .unwrap();
+    first.eval("(exit 4) &");
+    // Let both exit before anything waits for them
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert_eq!(second.eval("true | true"), 0);
+    assert_eq!(first.eval("wait $!"), 4);
+    assert_eq!(host_child.wait().unwrap().code(), Some(5));
+}